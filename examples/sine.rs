@@ -1,7 +1,5 @@
 extern crate rsoundio;
-extern crate rb;
 
-use rb::{RB, SpscRb, RbProducer, RbConsumer};
 use std::f32::consts::PI as PI32;
 use std::thread;
 use std::time::Duration;
@@ -9,11 +7,11 @@ use std::time::Duration;
 const BUF_SIZE: usize = 2048;
 
 fn main() {
-    let rb = SpscRb::new(BUF_SIZE);
-    let (producer, consumer) = (rb.producer(), rb.consumer());
     // create an audio context
     let mut sio = rsoundio::SoundIo::default();
     sio.set_name("rsoundio-example").unwrap();
+    let rb = sio.create_ring_buffer::<f32>(BUF_SIZE).unwrap();
+    let (producer, consumer) = rb.split();
     // connect to the default audio backend
     sio.connect().unwrap();
     let backend = sio.current_backend().unwrap();
@@ -38,23 +36,31 @@ fn main() {
             const A: f32 = 0.6;
             const CYCLE: usize = (48_000f32 / F) as usize;
 
+            if producer.free_count() < LEN {
+                thread::sleep(Duration::from_millis(1));
+                continue;
+            }
             let samples: Vec<f32> = (0..LEN)
                                         .map(|i| (W * (i + pos) as f32).sin() * A)
                                         .collect();
-            producer.write_blocking(&samples).unwrap();
+            producer.write(&samples);
             pos = (pos + LEN) % CYCLE;
         }
     });
 
     // register callbacks
-    out.register_write_callback(|out: rsoundio::OutStream,
-                                 min_frame_count: u32,
-                                 max_frame_count: u32| {
+    let mut buf = vec![0.0f32; BUF_SIZE];
+    out.register_write_callback(move |out: rsoundio::OutStream,
+                                      min_frame_count: u32,
+                                      max_frame_count: u32| {
         let mut frames_left = max_frame_count as usize;
-        let mut buf = vec![0.0f32; BUF_SIZE];
         while frames_left > 0 {
             let len = ::std::cmp::min(BUF_SIZE, frames_left);
-            consumer.read_blocking(&mut buf[..len]);
+            let cnt = consumer.read(&mut buf[..len]);
+            // fill up with silence if the producer can't keep up
+            for sample in &mut buf[cnt..len] {
+                *sample = 0.0;
+            }
             let left = buf[..len].iter().cloned().collect::<Vec<f32>>();
             let right = left.clone();
             let frames = vec![left, right];
@@ -0,0 +1,121 @@
+use std::f32::consts::SQRT_2;
+
+use ffi;
+use ffi::enums::SioChannelId;
+use base::{ChannelLayout, SioResult};
+use mixer::ChannelMixer;
+
+/// Returns the azimuth, counter-clockwise from the front, and the elevation
+/// of the speaker `channel` in `layout` in degrees.
+/// Channels without a position, e.g. LFE or aux channels, return `None`.
+fn speaker_position(channel: SioChannelId, layout: &ChannelLayout) -> Option<(f32, f32)> {
+    let has_side = layout.find_channel(SioChannelId::SideLeft).is_some() ||
+                   layout.find_channel(SioChannelId::SideRight).is_some();
+    let has_back = layout.find_channel(SioChannelId::BackLeft).is_some() ||
+                   layout.find_channel(SioChannelId::BackRight).is_some();
+    // 5.1 puts its surround channels at 110 degrees, 7.1 at 90 and 150 degrees
+    let side = if has_back { 90.0 } else { 110.0 };
+    let back = if has_side { 150.0 } else { 135.0 };
+    let position = match channel {
+        SioChannelId::FrontLeft => (30.0, 0.0),
+        SioChannelId::FrontRight => (-30.0, 0.0),
+        SioChannelId::FrontCenter => (0.0, 0.0),
+        SioChannelId::FrontLeftCenter => (15.0, 0.0),
+        SioChannelId::FrontRightCenter => (-15.0, 0.0),
+        SioChannelId::FrontLeftWide => (60.0, 0.0),
+        SioChannelId::FrontRightWide => (-60.0, 0.0),
+        SioChannelId::SideLeft => (side, 0.0),
+        SioChannelId::SideRight => (-side, 0.0),
+        SioChannelId::BackLeft => (back, 0.0),
+        SioChannelId::BackRight => (-back, 0.0),
+        SioChannelId::BackLeftCenter => (165.0, 0.0),
+        SioChannelId::BackRightCenter => (-165.0, 0.0),
+        SioChannelId::BackCenter => (180.0, 0.0),
+        SioChannelId::HeadphonesLeft => (90.0, 0.0),
+        SioChannelId::HeadphonesRight => (-90.0, 0.0),
+        SioChannelId::FrontLeftHigh | SioChannelId::TopFrontLeft => (30.0, 45.0),
+        SioChannelId::FrontRightHigh | SioChannelId::TopFrontRight => (-30.0, 45.0),
+        SioChannelId::FrontCenterHigh | SioChannelId::TopFrontCenter => (0.0, 45.0),
+        SioChannelId::TopFrontLeftCenter => (15.0, 45.0),
+        SioChannelId::TopFrontRightCenter => (-15.0, 45.0),
+        SioChannelId::TopSideLeft => (90.0, 45.0),
+        SioChannelId::TopSideRight => (-90.0, 45.0),
+        SioChannelId::TopBackLeft => (150.0, 45.0),
+        SioChannelId::TopBackRight => (-150.0, 45.0),
+        SioChannelId::TopBackCenter => (180.0, 45.0),
+        SioChannelId::TopCenter => (0.0, 90.0),
+        SioChannelId::BottomCenter => (0.0, -90.0),
+        SioChannelId::BottomLeftCenter => (15.0, -45.0),
+        SioChannelId::BottomRightCenter => (-15.0, -45.0),
+        _ => return None,
+    };
+    Some(position)
+}
+
+impl ChannelMixer {
+    /// Creates a first-order ambisonic decoder from the B-format channels
+    /// `SioChannelId::AmbisonicW`, `AmbisonicX`, `AmbisonicY` and `AmbisonicZ` of `input`
+    /// to the speakers of `output`, e.g. quad, 5.1, 7.1 or octagonal.
+    /// Use `OutStream::write_mixed` to write the decoded frames.
+    ///
+    /// The B-format is expected in the FuMa convention, i.e. `W` is attenuated by 3 dB.
+    /// The speaker angles are derived from the channel ids. Layouts with less than three
+    /// speakers, e.g. stereo, are decoded with two virtual cardioids pointing left and right.
+    /// Missing `X`, `Y` or `Z` channels are treated as silence and channels without
+    /// a position, e.g. LFE, stay silent.
+    ///
+    /// Returns `ffi::enums::SioError::Invalid` if `input` doesn't contain `AmbisonicW`.
+    pub fn ambisonic_decoder(input: &ChannelLayout,
+                             output: &ChannelLayout)
+                             -> SioResult<ChannelMixer> {
+        let w = try!(input.find_channel(SioChannelId::AmbisonicW)
+                          .ok_or(ffi::enums::SioError::Invalid)) as usize;
+        let x = input.find_channel(SioChannelId::AmbisonicX).map(|idx| idx as usize);
+        let y = input.find_channel(SioChannelId::AmbisonicY).map(|idx| idx as usize);
+        let z = input.find_channel(SioChannelId::AmbisonicZ).map(|idx| idx as usize);
+        let speakers: Vec<(usize, (f32, f32))> = output.channels()
+                                                       .iter()
+                                                       .enumerate()
+                                                       .filter_map(|(idx, &channel)| {
+                                                           speaker_position(channel, output)
+                                                               .map(|position| (idx, position))
+                                                       })
+                                                       .collect();
+        let mut mixer = ChannelMixer::silent(input.channel_count() as usize,
+                                             output.channel_count() as usize);
+        if speakers.len() < 3 {
+            for &(out_idx, (azimuth, _)) in &speakers {
+                // cardioids pointing to the side of the speaker, the center gets the omni part
+                let direction = if azimuth > 0.0 {
+                    1.0
+                } else if azimuth < 0.0 {
+                    -1.0
+                } else {
+                    0.0
+                };
+                let scale = if direction == 0.0 { 1.0 } else { 0.5 };
+                mixer.set_gain(w, out_idx, SQRT_2 * scale);
+                if let Some(y) = y {
+                    mixer.set_gain(y, out_idx, direction * scale);
+                }
+            }
+            return Ok(mixer);
+        }
+        // projection decoder, preserves the pressure of a source for regular layouts
+        let scale = 1.0 / speakers.len() as f32;
+        for &(out_idx, (azimuth, elevation)) in &speakers {
+            let (azimuth, elevation) = (azimuth.to_radians(), elevation.to_radians());
+            mixer.set_gain(w, out_idx, SQRT_2 * scale);
+            if let Some(x) = x {
+                mixer.set_gain(x, out_idx, 2.0 * scale * azimuth.cos() * elevation.cos());
+            }
+            if let Some(y) = y {
+                mixer.set_gain(y, out_idx, 2.0 * scale * azimuth.sin() * elevation.cos());
+            }
+            if let Some(z) = z {
+                mixer.set_gain(z, out_idx, 2.0 * scale * elevation.sin());
+            }
+        }
+        Ok(mixer)
+    }
+}
@@ -0,0 +1,197 @@
+use std::marker::PhantomData;
+use std::ops::{Index, IndexMut};
+use std::{mem, slice};
+
+use ffi;
+use sample::Sample;
+
+/// A typed view on the samples of a single channel in device memory.
+///
+/// The samples of a channel are not necessarily contiguous,
+/// the view takes care of the `step` between them.
+pub struct ChannelArea<'a, T: 'a> {
+    ptr: *mut u8,
+    step: usize,
+    len: usize,
+    phantom: PhantomData<&'a mut T>,
+}
+impl<'a, T: Sample + 'a> ChannelArea<'a, T> {
+    /// Returns `None` if the samples are not aligned for `T`
+    /// or if `T` is larger than the distance between two samples.
+    pub(crate) fn new(area: &ffi::SoundIoChannelArea, len: usize) -> Option<Self> {
+        let step = area.step as usize;
+        let align = mem::align_of::<T>();
+        if len > 0 && (mem::size_of::<T>() > step || !step.is_multiple_of(align) ||
+                       !(area.ptr as usize).is_multiple_of(align)) {
+            return None;
+        }
+        Some(ChannelArea {
+            ptr: area.ptr as *mut u8,
+            step: step,
+            len: len,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Returns the number of samples in the channel.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the channel contains no samples.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns an iterator over the samples.
+    pub fn iter(&self) -> ChannelAreaIter<'_, T> {
+        ChannelAreaIter {
+            ptr: self.ptr,
+            step: self.step,
+            remaining: self.len,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns an iterator that allows modifying each sample.
+    pub fn iter_mut(&mut self) -> ChannelAreaIterMut<'_, T> {
+        ChannelAreaIterMut {
+            ptr: self.ptr,
+            step: self.step,
+            remaining: self.len,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Copies the samples of `src` into the channel and returns the number of
+    /// copied samples, which is the minimum of both lengths.
+    pub fn copy_from_slice(&mut self, src: &[T]) -> usize {
+        let mut cnt = 0;
+        for (dst, &sample) in self.iter_mut().zip(src.iter()) {
+            *dst = sample;
+            cnt += 1;
+        }
+        cnt
+    }
+
+    fn sample_ptr(&self, idx: usize) -> *mut T {
+        assert!(idx < self.len,
+                "index out of bounds: the len is {} but the index is {}",
+                self.len,
+                idx);
+        (self.ptr as usize + self.step * idx) as *mut T
+    }
+}
+impl<'a, T: Sample + 'a> Index<usize> for ChannelArea<'a, T> {
+    type Output = T;
+
+    fn index(&self, idx: usize) -> &T {
+        unsafe { &*self.sample_ptr(idx) }
+    }
+}
+impl<'a, T: Sample + 'a> IndexMut<usize> for ChannelArea<'a, T> {
+    fn index_mut(&mut self, idx: usize) -> &mut T {
+        unsafe { &mut *self.sample_ptr(idx) }
+    }
+}
+impl<'a, 'b, T: Sample + 'a> IntoIterator for &'b ChannelArea<'a, T> {
+    type Item = &'b T;
+    type IntoIter = ChannelAreaIter<'b, T>;
+
+    fn into_iter(self) -> ChannelAreaIter<'b, T> {
+        self.iter()
+    }
+}
+impl<'a, 'b, T: Sample + 'a> IntoIterator for &'b mut ChannelArea<'a, T> {
+    type Item = &'b mut T;
+    type IntoIter = ChannelAreaIterMut<'b, T>;
+
+    fn into_iter(self) -> ChannelAreaIterMut<'b, T> {
+        self.iter_mut()
+    }
+}
+
+/// Iterator over the samples of a `ChannelArea`.
+pub struct ChannelAreaIter<'a, T: 'a> {
+    ptr: *mut u8,
+    step: usize,
+    remaining: usize,
+    phantom: PhantomData<&'a T>,
+}
+impl<'a, T: 'a> Iterator for ChannelAreaIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let sample = unsafe { &*(self.ptr as *const T) };
+        self.ptr = (self.ptr as usize + self.step) as *mut u8;
+        self.remaining -= 1;
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+impl<'a, T: 'a> ExactSizeIterator for ChannelAreaIter<'a, T> {}
+
+/// Mutable iterator over the samples of a `ChannelArea`.
+pub struct ChannelAreaIterMut<'a, T: 'a> {
+    ptr: *mut u8,
+    step: usize,
+    remaining: usize,
+    phantom: PhantomData<&'a mut T>,
+}
+impl<'a, T: 'a> Iterator for ChannelAreaIterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<&'a mut T> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let sample = unsafe { &mut *(self.ptr as *mut T) };
+        self.ptr = (self.ptr as usize + self.step) as *mut u8;
+        self.remaining -= 1;
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+impl<'a, T: 'a> ExactSizeIterator for ChannelAreaIterMut<'a, T> {}
+
+/// Iterator over the `ChannelArea`s of all channels, returned from `WriteGuard::channels`.
+pub struct ChannelAreas<'a, T: 'a> {
+    areas: slice::Iter<'a, ffi::SoundIoChannelArea>,
+    len: usize,
+    phantom: PhantomData<&'a mut T>,
+}
+impl<'a, T: Sample + 'a> ChannelAreas<'a, T> {
+    /// Returns `None` if the samples of any channel are not aligned for `T`.
+    pub(crate) fn new(areas: &'a [ffi::SoundIoChannelArea], len: usize) -> Option<Self> {
+        if areas.iter().any(|area| ChannelArea::<T>::new(area, len).is_none()) {
+            return None;
+        }
+        Some(ChannelAreas {
+            areas: areas.iter(),
+            len: len,
+            phantom: PhantomData,
+        })
+    }
+}
+impl<'a, T: Sample + 'a> Iterator for ChannelAreas<'a, T> {
+    type Item = ChannelArea<'a, T>;
+
+    fn next(&mut self) -> Option<ChannelArea<'a, T>> {
+        // the areas of different channels never overlap and were validated on creation
+        self.areas.next().and_then(|area| ChannelArea::new(area, self.len))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.areas.size_hint()
+    }
+}
+impl<'a, T: Sample + 'a> ExactSizeIterator for ChannelAreas<'a, T> {}
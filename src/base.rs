@@ -1,26 +1,188 @@
+use std::any::Any;
+use std::cell::Cell;
 use std::fmt::Display;
-use std::os::raw::c_int;
+use std::str::FromStr;
+use std::hash::{Hash, Hasher};
+use std::os::raw::{c_char, c_int, c_void};
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::{cmp, mem, ptr, slice};
 use std::ffi::CString;
+use std::sync::{Condvar, Mutex};
 
 use ffi;
-use stream::OutStream;
+use stream::{OutStream, InStream};
+use ring_buffer::RingBuffer;
+use sample::best_format;
+#[cfg(feature = "async")]
+use events::Signal;
 
-const MAX_CHANNELS: u32 = 24;
+pub(crate) const MAX_CHANNELS: u32 = 24;
+
+/// The builtin channel layouts in the order of libsoundio.
+const BUILTIN_LAYOUT_IDS: [ffi::enums::SioChannelLayoutId; 26] = [
+    ffi::enums::SioChannelLayoutId::Mono,
+    ffi::enums::SioChannelLayoutId::Stereo,
+    ffi::enums::SioChannelLayoutId::TwoPointOne,
+    ffi::enums::SioChannelLayoutId::ThreePointZero,
+    ffi::enums::SioChannelLayoutId::ThreePointZeroBack,
+    ffi::enums::SioChannelLayoutId::ThreePointOne,
+    ffi::enums::SioChannelLayoutId::FourPointZero,
+    ffi::enums::SioChannelLayoutId::Quad,
+    ffi::enums::SioChannelLayoutId::QuadSide,
+    ffi::enums::SioChannelLayoutId::FourPointOne,
+    ffi::enums::SioChannelLayoutId::FivePointZeroBack,
+    ffi::enums::SioChannelLayoutId::FivePointZeroSide,
+    ffi::enums::SioChannelLayoutId::FivePointOne,
+    ffi::enums::SioChannelLayoutId::FivePointOneBack,
+    ffi::enums::SioChannelLayoutId::SixPointZeroSide,
+    ffi::enums::SioChannelLayoutId::SixPointZeroFront,
+    ffi::enums::SioChannelLayoutId::Hexagonal,
+    ffi::enums::SioChannelLayoutId::SixPointOne,
+    ffi::enums::SioChannelLayoutId::SixPointOneBack,
+    ffi::enums::SioChannelLayoutId::SixPointOneFront,
+    ffi::enums::SioChannelLayoutId::SevenPointZero,
+    ffi::enums::SioChannelLayoutId::SevenPointZeroFront,
+    ffi::enums::SioChannelLayoutId::SevenPointOne,
+    ffi::enums::SioChannelLayoutId::SevenPointOneWide,
+    ffi::enums::SioChannelLayoutId::SevenPointOneWideBack,
+    ffi::enums::SioChannelLayoutId::Octagonal,
+];
 
 /// Result wrapper that always contains a `ffi::enums::SioError` in error case.
 pub type SioResult<T> = Result<T, ffi::enums::SioError>;
 
+// Panics must not unwind into libsoundio. They are caught and
+// resumed as soon as `flush_events` or `wait_events` return.
+extern "C" fn devices_change_wrapper(raw_sio: *mut ffi::SoundIo) {
+    let shared = unsafe { &*((*raw_sio).userdata as *const SharedCallbacks) };
+    let callbacks: &mut SoundIoCallbacks = unsafe { &mut *shared.local };
+    if let Some(ref mut f) = callbacks.devices_change {
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(f)) {
+            callbacks.panic.set(Some(payload));
+        }
+    }
+}
+
+extern "C" fn backend_disconnect_wrapper(raw_sio: *mut ffi::SoundIo, error: ffi::enums::SioError) {
+    let shared = unsafe { &*((*raw_sio).userdata as *const SharedCallbacks) };
+    let callbacks: &mut SoundIoCallbacks = unsafe { &mut *shared.local };
+    if let Some(ref mut f) = callbacks.backend_disconnect {
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| f(error))) {
+            callbacks.panic.set(Some(payload));
+        }
+    }
+}
+
+extern "C" fn events_signal_wrapper(raw_sio: *mut ffi::SoundIo) {
+    let shared = unsafe { &*((*raw_sio).userdata as *const SharedCallbacks) };
+    shared.signal();
+}
+
+/// The callbacks that are only called on the thread that calls `flush_events` or `wait_events`.
+struct SoundIoCallbacks {
+    devices_change: Option<Box<FnMut()>>,
+    backend_disconnect: Option<Box<FnMut(ffi::enums::SioError)>>,
+    // payload of a panic caught in `devices_change` or `backend_disconnect`
+    panic: Cell<Option<Box<Any + Send>>>,
+}
+impl Default for SoundIoCallbacks {
+    fn default() -> Self {
+        SoundIoCallbacks {
+            devices_change: None,
+            backend_disconnect: None,
+            panic: Cell::new(None),
+        }
+    }
+}
+
+/// The context callbacks that `SoundIo::events` chains onto and restores afterwards.
+#[cfg(feature = "async")]
+pub(crate) struct ContextCallbacks {
+    pub devices_change: Option<Box<FnMut()>>,
+    pub backend_disconnect: Option<Box<FnMut(ffi::enums::SioError)>>,
+    // the raw callbacks, which are libsoundio's defaults if nothing was registered
+    on_devices_change: Option<extern "C" fn(*mut ffi::SoundIo)>,
+    on_backend_disconnect: Option<extern "C" fn(*mut ffi::SoundIo, ffi::enums::SioError)>,
+}
+
+/// The `userdata` of the context. It is also used by the thread that calls
+/// `on_events_signal`, so it is only accessed through shared references.
+struct SharedCallbacks {
+    // only dereferenced on the thread that owns the `SoundIo`
+    local: *mut SoundIoCallbacks,
+    events_signal: Mutex<Option<Box<Fn() + Send + Sync>>>,
+    // set by every signal until `SoundIo::wait_signal` returns, so no wakeup is lost
+    signaled: Mutex<bool>,
+    signaled_cond: Condvar,
+    // wakes the tasks waiting in `SoundIo::events` or `SoundIo::wait_events_async`
+    #[cfg(feature = "async")]
+    signal: Signal,
+}
+impl SharedCallbacks {
+    fn signal(&self) {
+        {
+            // a poisoned lock still holds a valid callback
+            let events_signal = self.events_signal.lock().unwrap_or_else(|err| err.into_inner());
+            if let Some(ref f) = *events_signal {
+                // Called from an unknown thread, so there is nobody to resume the panic.
+                let _ = panic::catch_unwind(AssertUnwindSafe(f));
+            }
+        }
+        *self.signaled.lock().unwrap_or_else(|err| err.into_inner()) = true;
+        self.signaled_cond.notify_all();
+        #[cfg(feature = "async")]
+        self.signal.notify();
+    }
+}
+
 /// The base struct which can connect to various audio backends
 /// and provides methods to get in-/output `Device`s.
+///
+/// Devices and streams borrow the context, so it can't be dropped
+/// while they are still alive:
+///
+/// ```compile_fail
+/// let sio = rsoundio::SoundIo::default();
+/// sio.connect().unwrap();
+/// sio.flush_events();
+/// let dev = sio.default_output_device().unwrap();
+/// drop(sio);
+/// dev.create_outstream();
+/// ```
 pub struct SoundIo {
     context: *mut ffi::SoundIo,
+    callbacks: Box<SoundIoCallbacks>,
+    shared: Box<SharedCallbacks>,
     name: CString,
 }
 impl SoundIo {
     pub fn new<S: Into<String>>(name: S) -> Self {
+        SoundIo::with_name(CString::new(name.into()).unwrap())
+    }
+
+    fn with_name(name: CString) -> Self {
+        let mut callbacks = Box::new(SoundIoCallbacks::default());
+        // the boxes never move, even if `SoundIo` does
+        let shared = Box::new(SharedCallbacks {
+            local: &mut *callbacks,
+            events_signal: Mutex::new(None),
+            signaled: Mutex::new(false),
+            signaled_cond: Condvar::new(),
+            #[cfg(feature = "async")]
+            signal: Signal::default(),
+        });
+        let context = unsafe { ffi::soundio_create() };
+        unsafe {
+            (*context).userdata = &*shared as *const SharedCallbacks as *mut c_void;
+            // installed before connecting, libsoundio reads it from its own threads afterwards
+            (*context).on_events_signal = Some(events_signal_wrapper);
+        }
         SoundIo {
-            context: unsafe { ffi::soundio_create() },
-            name: CString::new(name.into()).unwrap(),
+            context: context,
+            callbacks: callbacks,
+            shared: shared,
+            name: name,
         }
     }
 
@@ -100,13 +262,17 @@ impl SoundIo {
     ///
     /// When you call this, the following callbacks might be called:
     ///
-    /// - `on_device_change`
-    /// - `on_backend_disconnect`
+    /// - `devices_change_callback`
+    /// - `backend_disconnect_callback`
     ///
     /// Note that if you do not care about learning about updated devices,
     /// you might call this function only once ever and never call `wait_events`.
+    ///
+    /// If the `devices_change_callback` or the `backend_disconnect_callback`
+    /// panics, the panic is resumed after the events were flushed.
     pub fn flush_events(&self) {
         unsafe { ffi::soundio_flush_events(self.context) }
+        self.resume_callback_panic()
     }
 
     /// This function calls `flush_events` then blocks until another event
@@ -114,11 +280,62 @@ impl SoundIo {
     /// Be ready for spurious wakeups.
     pub fn wait_events(&self) {
         unsafe { ffi::soundio_wait_events(self.context) }
+        self.resume_callback_panic()
+    }
+
+    fn resume_callback_panic(&self) {
+        if let Some(payload) = self.callbacks.panic.take() {
+            panic::resume_unwind(payload)
+        }
     }
 
     /// Makes `wait_events` stop blocking.
+    /// The `events_signal_callback` is called as well.
     pub fn wakeup(&self) {
         unsafe { ffi::soundio_wakeup(self.context) }
+        // libsoundio doesn't call `on_events_signal` on a wakeup
+        self.shared.signal();
+    }
+
+    /// Blocks until the `events_signal_callback` would be called, or returns at once
+    /// if that happened since the last call. Unlike `wait_events` it doesn't miss a
+    /// `wakeup` from another thread right before blocking. Call `flush_events` afterwards.
+    pub(crate) fn wait_signal(&self) {
+        let mut signaled = self.shared.signaled.lock().unwrap_or_else(|err| err.into_inner());
+        while !*signaled {
+            signaled = self.shared
+                           .signaled_cond
+                           .wait(signaled)
+                           .unwrap_or_else(|err| err.into_inner());
+        }
+        *signaled = false;
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn signal(&self) -> &Signal {
+        &self.shared.signal
+    }
+
+    /// Removes the registered `on_devices_change` and `on_backend_disconnect` callbacks.
+    #[cfg(feature = "async")]
+    pub(crate) fn take_callbacks(&mut self) -> ContextCallbacks {
+        ContextCallbacks {
+            devices_change: self.callbacks.devices_change.take(),
+            backend_disconnect: self.callbacks.backend_disconnect.take(),
+            on_devices_change: unsafe { (*self.context).on_devices_change },
+            on_backend_disconnect: unsafe { (*self.context).on_backend_disconnect },
+        }
+    }
+
+    /// Replaces the context callbacks with the ones returned by `take_callbacks`.
+    #[cfg(feature = "async")]
+    pub(crate) fn restore_callbacks(&mut self, callbacks: ContextCallbacks) {
+        self.callbacks.devices_change = callbacks.devices_change;
+        self.callbacks.backend_disconnect = callbacks.backend_disconnect;
+        unsafe {
+            (*self.context).on_devices_change = callbacks.on_devices_change;
+            (*self.context).on_backend_disconnect = callbacks.on_backend_disconnect;
+        }
     }
 
     /// If necessary you can manually trigger a device rescan. Normally you will
@@ -177,7 +394,7 @@ impl SoundIo {
     /// `idx` must be in [0, `input_device_count`)
     /// Returns `None` if you never called `flush_events` or if you provide
     /// invalid parameter values.
-    pub fn input_device(&self, idx: u32) -> Option<Device> {
+    pub fn input_device(&self, idx: u32) -> Option<Device<'_>> {
         let dev_ptr = unsafe { ffi::soundio_get_input_device(self.context, idx as c_int) };
         if dev_ptr.is_null() {
             None
@@ -190,7 +407,7 @@ impl SoundIo {
     /// `idx` must be in [0, `output_device_count`)
     /// Returns `None` if you never called `flush_events` or if you provide
     /// invalid parameter values.
-    pub fn output_device(&self, idx: u32) -> Option<Device> {
+    pub fn output_device(&self, idx: u32) -> Option<Device<'_>> {
         let dev_ptr = unsafe { ffi::soundio_get_output_device(self.context, idx as c_int) };
         if dev_ptr.is_null() {
             None
@@ -221,13 +438,13 @@ impl SoundIo {
 
     /// Returns the default output `Device` of the backend.
     /// `None` if you aren't connected to a backend.
-    pub fn default_output_device(&self) -> Option<Device> {
+    pub fn default_output_device(&self) -> Option<Device<'_>> {
         self.default_output_device_index().and_then(|idx| self.output_device(idx))
     }
 
     /// Returns the default input `Device` of the backend.
     /// `None` if you aren't connected to a backend.
-    pub fn default_input_device(&self) -> Option<Device> {
+    pub fn default_input_device(&self) -> Option<Device<'_>> {
         self.default_input_device_index().and_then(|idx| self.input_device(idx))
     }
 
@@ -249,13 +466,81 @@ impl SoundIo {
     pub fn name(&self) -> SioResult<String> {
         unsafe { ffi::utils::ptr_to_string((*self.context).app_name) }
     }
+
+    /// Creates a lock-free single-reader single-writer `RingBuffer` that holds
+    /// at least `capacity` elements of type `T`.
+    /// The actual capacity might be greater for alignment purposes.
+    ///
+    /// Returns `ffi::enums::SioError::Invalid` if `T` is zero-sized or `capacity`
+    /// is either zero or too large and `ffi::enums::SioError::NoMem` if memory could not be allocated.
+    pub fn create_ring_buffer<T: Copy>(&self, capacity: usize) -> SioResult<RingBuffer<T>> {
+        let bytes = capacity.checked_mul(mem::size_of::<T>()).unwrap_or(0);
+        if bytes == 0 || bytes > c_int::MAX as usize {
+            return Err(ffi::enums::SioError::Invalid);
+        }
+        let buffer_ptr = unsafe { ffi::soundio_ring_buffer_create(self.context, bytes as c_int) };
+        if buffer_ptr.is_null() {
+            Err(ffi::enums::SioError::NoMem)
+        } else {
+            Ok(RingBuffer::new(buffer_ptr))
+        }
+    }
+
+    /// Registers the given callback as `on_devices_change` callback.
+    /// It is called when the list of devices changes and only during a call to
+    /// `flush_events` or `wait_events`, thus from the same thread.
+    pub fn register_devices_change_callback<D>(&mut self, callback: D)
+        where D: FnMut() + 'static
+    {
+        self.callbacks.devices_change = Some(Box::new(callback));
+        unsafe { (*self.context).on_devices_change = Some(devices_change_wrapper) }
+    }
+
+    /// Registers the given callback as `on_backend_disconnect` callback.
+    /// It is called when the backend disconnects, for example when the JACK
+    /// server shuts down. When this happens, listing devices and opening
+    /// streams will always fail with `ffi::enums::SioError::BackendDisconnected`.
+    /// This callback is only called during a call to `flush_events` or `wait_events`.
+    ///
+    /// If you do not supply a callback, the default will crash your program
+    /// with an error message. This callback is also called when the thread that
+    /// retrieves device information runs into an unrecoverable condition such
+    /// as running out of memory.
+    ///
+    /// Possible errors:
+    ///
+    /// - `ffi::enums::SioError::BackendDisconnected`
+    /// - `ffi::enums::SioError::NoMem`
+    /// - `ffi::enums::SioError::SystemResources`
+    /// - `ffi::enums::SioError::OpeningDevice` - unexpected problem accessing device
+    ///   information
+    pub fn register_backend_disconnect_callback<B>(&mut self, callback: B)
+        where B: FnMut(ffi::enums::SioError) + 'static
+    {
+        self.callbacks.backend_disconnect = Some(Box::new(callback));
+        unsafe { (*self.context).on_backend_disconnect = Some(backend_disconnect_wrapper) }
+    }
+
+    /// Registers the given callback as `on_events_signal` callback.
+    /// It is called from an unknown thread when `wait_events` would be woken up,
+    /// e.g. by `wakeup`, so you should not use it to call any `SoundIo` methods.
+    /// You may use this to signal a condition variable to wake up.
+    /// Panics in this callback are caught and ignored.
+    ///
+    /// The callback can be replaced at any time, even while connected.
+    pub fn register_events_signal_callback<S>(&mut self, callback: S)
+        where S: Fn() + Send + Sync + 'static
+    {
+        let mut events_signal = self.shared
+                                    .events_signal
+                                    .lock()
+                                    .unwrap_or_else(|err| err.into_inner());
+        *events_signal = Some(Box::new(callback));
+    }
 }
 impl Default for SoundIo {
     fn default() -> Self {
-        SoundIo {
-            context: unsafe { ffi::soundio_create() },
-            name: CString::new("rsoundio").unwrap(),
-        }
+        SoundIo::with_name(CString::new("rsoundio").unwrap())
     }
 }
 impl Drop for SoundIo {
@@ -267,21 +552,48 @@ impl Drop for SoundIo {
     }
 }
 
-/// Provides methods on channel layouts. Layout variants are defined
-/// in `ffi::enums::SioChannelLayoutId`.
-#[derive(Debug)]
+/// A channel layout, i.e. a list of up to 24 channels and an optional name.
+/// Builtin variants are defined in `ffi::enums::SioChannelLayoutId`.
+///
+/// Two layouts are equal if they contain the same channels in the same order,
+/// the name is ignored.
+#[derive(Clone, Debug)]
 pub struct ChannelLayout {
-    layout: *const ffi::SoundIoChannelLayout,
+    name: Option<String>,
+    channels: Vec<ffi::enums::SioChannelId>,
 }
 impl ChannelLayout {
-    pub fn new(raw_layout: *const ffi::SoundIoChannelLayout) -> Self {
-        ChannelLayout { layout: raw_layout }
+    /// Copies the raw layout.
+    pub(crate) fn new(raw_layout: *const ffi::SoundIoChannelLayout) -> Self {
+        let raw_layout = unsafe { &*raw_layout };
+        let count = cmp::min(cmp::max(raw_layout.channel_count, 0) as usize,
+                             MAX_CHANNELS as usize);
+        ChannelLayout {
+            name: ffi::utils::ptr_to_string(raw_layout.name).ok(),
+            channels: raw_layout.channels[..count].to_vec(),
+        }
+    }
+
+    /// Creates a layout from a list of `channels`.
+    /// The name is set if the channels match a builtin layout.
+    ///
+    /// Returns `None` if there are more than 24 channels.
+    pub fn from_channels(channels: &[ffi::enums::SioChannelId]) -> Option<ChannelLayout> {
+        if channels.len() > MAX_CHANNELS as usize {
+            return None;
+        }
+        let mut layout = ChannelLayout {
+            name: None,
+            channels: channels.to_vec(),
+        };
+        layout.detect_builtin();
+        Some(layout)
     }
 
     /// Returns a builtin channel layout or `None` if
     /// `idx` *not* in [0, `SoundIo::channel_layout_builtin_count`).
-    pub fn builtin(idx: u32) -> Option<Self> {
-        if idx < SoundIo::channel_layout_builtin_count() as u32 {
+    pub fn builtin(idx: u32) -> Option<ChannelLayout> {
+        if idx < SoundIo::channel_layout_builtin_count() {
             Some(ChannelLayout::new(unsafe {
                 ffi::soundio_channel_layout_get_builtin(idx as c_int)
             }))
@@ -290,32 +602,83 @@ impl ChannelLayout {
         }
     }
 
+    /// Returns the builtin channel layout `id`.
+    pub fn from_id(id: ffi::enums::SioChannelLayoutId) -> ChannelLayout {
+        ChannelLayout::new(unsafe { ffi::soundio_channel_layout_get_builtin(id as c_int) })
+    }
+
+    /// Returns the id of the builtin layout with the same channels,
+    /// or `None` if the layout is not a builtin one.
+    pub fn id(&self) -> Option<ffi::enums::SioChannelLayoutId> {
+        // `detect_builtin` points the name to the one of the builtin layout
+        let name = self.to_raw().name;
+        if name.is_null() {
+            return None;
+        }
+        BUILTIN_LAYOUT_IDS.iter().cloned().find(|&id| {
+            unsafe { (*ffi::soundio_channel_layout_get_builtin(id as c_int)).name == name }
+        })
+    }
+
     /// Get the default builtin channel layout for the given number of channels.
-    pub fn default(channel_count: u32) -> Option<Self> {
+    /// Returns `None` if there is no default layout for `channel_count`.
+    pub fn default(channel_count: u32) -> Option<ChannelLayout> {
         if channel_count < MAX_CHANNELS {
-            Some(ChannelLayout::new(unsafe {
-                ffi::soundio_channel_layout_get_default(channel_count as c_int)
-            }))
+            let layout = unsafe { ffi::soundio_channel_layout_get_default(channel_count as c_int) };
+            if layout.is_null() {
+                None
+            } else {
+                Some(ChannelLayout::new(layout))
+            }
         } else {
             None
         }
     }
 
+    /// Returns the raw layout that is passed to libsoundio.
+    ///
+    /// The name of a builtin layout points to the static builtin name,
+    /// any other name is dropped, because libsoundio doesn't own it.
+    pub(crate) fn to_raw(&self) -> ffi::SoundIoChannelLayout {
+        let mut raw_layout = ffi::SoundIoChannelLayout {
+            name: ptr::null(),
+            channel_count: self.channels.len() as c_int,
+            channels: [ffi::enums::SioChannelId::Invalid; MAX_CHANNELS as usize],
+        };
+        raw_layout.channels[..self.channels.len()].copy_from_slice(&self.channels);
+        unsafe { ffi::soundio_channel_layout_detect_builtin(&mut raw_layout) };
+        raw_layout
+    }
+
+    /// Returns the name of the layout.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Sets the name of the layout.
+    pub fn set_name<T: Into<String>>(&mut self, name: T) {
+        self.name = Some(name.into())
+    }
+
+    /// Returns the channels of the layout.
+    pub fn channels(&self) -> &[ffi::enums::SioChannelId] {
+        &self.channels
+    }
+
     /// Return the index of `channel` in the layout, or `None` if not found.
     pub fn find_channel(&self, channel: ffi::enums::SioChannelId) -> Option<u32> {
-        match unsafe { ffi::soundio_channel_layout_find_channel(self.layout, channel) } {
-            -1 => None,
-            idx => Some(idx as u32),
-        }
+        self.channels.iter().position(|&c| c == channel).map(|idx| idx as u32)
     }
 
-    /// Populates the name field of layout if it matches a builtin one.
+    /// Sets the name of the layout if it matches a builtin one.
+    /// Returns `true` if there is a match.
     pub fn detect_builtin(&mut self) -> bool {
-        // This is a hack because of the transmute.
-        unsafe {
-            let mut_layout: *mut ffi::SoundIoChannelLayout = ::std::mem::transmute(self.layout);
-            ffi::soundio_channel_layout_detect_builtin(mut_layout) == 1
+        let raw_layout = self.to_raw();
+        if raw_layout.name.is_null() {
+            return false;
         }
+        self.name = ffi::utils::ptr_to_string(raw_layout.name).ok();
+        true
     }
 
     /// Iterates over `preferred_layouts`. Returns the first channel layout in
@@ -325,13 +688,8 @@ impl ChannelLayout {
     pub fn best_matching_channel_layout(preferred_layouts: &[ChannelLayout],
                                         available_layouts: &[ChannelLayout])
                                         -> Option<ChannelLayout> {
-        // do some magic with the slices
-        let raw_preferred_layouts: Vec<_> = preferred_layouts.iter()
-                                                             .map(|l| unsafe { (*l.layout) })
-                                                             .collect();
-        let raw_available_layouts: Vec<_> = available_layouts.iter()
-                                                             .map(|l| unsafe { (*l.layout) })
-                                                             .collect();
+        let raw_preferred_layouts: Vec<_> = preferred_layouts.iter().map(|l| l.to_raw()).collect();
+        let raw_available_layouts: Vec<_> = available_layouts.iter().map(|l| l.to_raw()).collect();
         let layout_ptr = unsafe {
             ffi::soundio_best_matching_channel_layout(raw_preferred_layouts.as_ptr(),
                                                       preferred_layouts.len() as c_int,
@@ -341,43 +699,117 @@ impl ChannelLayout {
         if layout_ptr.is_null() {
             None
         } else {
-            Some(ChannelLayout::new(layout_ptr))
+            // `layout_ptr` points into the temporary copy of `available_layouts`
+            let idx = (layout_ptr as usize - raw_available_layouts.as_ptr() as usize) /
+                      mem::size_of::<ffi::SoundIoChannelLayout>();
+            Some(available_layouts[idx].clone())
         }
     }
 
     /// Returns the number of channels in the layout.
     pub fn channel_count(&self) -> u32 {
-        unsafe { (*self.layout).channel_count as u32 }
+        self.channels.len() as u32
     }
 }
 impl PartialEq for ChannelLayout {
-    fn eq(&self, other: &Self) -> bool {
-        unsafe { ffi::soundio_channel_layout_equal(self.layout, other.layout) == 1u8 }
+    fn eq(&self, other: &ChannelLayout) -> bool {
+        self.channels == other.channels
+    }
+}
+impl Eq for ChannelLayout {}
+impl Hash for ChannelLayout {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.channels.hash(state)
     }
+}
+impl FromStr for ChannelLayout {
+    type Err = ffi::enums::SioError;
 
-    fn ne(&self, other: &Self) -> bool {
-        !self.eq(other)
+    /// Parses the name of a builtin layout, e.g. `"5.1"` or `"stereo"`,
+    /// or a comma separated list of channels, e.g. `"FL,FR,FC,LFE,SL,SR"`.
+    /// Channels can be given by their name or their abbreviation.
+    ///
+    /// Returns `ffi::enums::SioError::Invalid` if a channel is unknown
+    /// or there are more than 24 channels.
+    fn from_str(s: &str) -> SioResult<ChannelLayout> {
+        let s = s.trim();
+        let builtin = BUILTIN_LAYOUT_IDS.iter()
+                                        .map(|&id| ChannelLayout::from_id(id))
+                                        .find(|layout| {
+                                            layout.name()
+                                                  .is_some_and(|name| name.eq_ignore_ascii_case(s))
+                                        });
+        if let Some(layout) = builtin {
+            return Ok(layout);
+        }
+        let mut channels = Vec::new();
+        for name in s.split(',').map(|name| name.trim()) {
+            let channel = unsafe {
+                ffi::soundio_parse_channel_id(name.as_ptr() as *const c_char, name.len() as c_int)
+            };
+            if channel == ffi::enums::SioChannelId::Invalid {
+                return Err(ffi::enums::SioError::Invalid);
+            }
+            channels.push(channel);
+        }
+        ChannelLayout::from_channels(&channels).ok_or(ffi::enums::SioError::Invalid)
     }
 }
 impl Display for ChannelLayout {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
-        let str_ptr = unsafe { (*self.layout).name };
-        write!(f, "{}", ffi::utils::ptr_to_string(str_ptr).unwrap())
+        match self.name {
+            Some(ref name) => write!(f, "{}", name),
+            None => {
+                let names: Vec<String> = self.channels.iter().map(|c| c.to_string()).collect();
+                write!(f, "{}", names.join(", "))
+            }
+        }
+    }
+}
+
+/// A range of sample rates a `Device` supports.
+/// Both `min` and `max` are inclusive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SampleRateRange {
+    pub min: u32,
+    pub max: u32,
+}
+impl SampleRateRange {
+    /// Returns `true` if `sample_rate` is within the range.
+    pub fn contains(&self, sample_rate: u32) -> bool {
+        self.min <= sample_rate && sample_rate <= self.max
     }
 }
 
 /// Provides methods on an audio device.
+///
+/// A device can't outlive the `SoundIo` context it was returned from.
 #[derive(Debug)]
-pub struct Device {
+pub struct Device<'a> {
     device: *mut ffi::SoundIoDevice,
+    phantom: PhantomData<&'a SoundIo>,
 }
-impl Device {
-    pub fn new(dev_ptr: *mut ffi::SoundIoDevice) -> Self {
-        Device { device: dev_ptr }
+impl<'a> Device<'a> {
+    /// Takes ownership of a reference to the device,
+    /// it is released when the `Device` is dropped.
+    /// `dev_ptr` must be valid for the lifetime `'a`, i.e. belong to a `SoundIo` that outlives it.
+    pub(crate) fn new(dev_ptr: *mut ffi::SoundIoDevice) -> Self {
+        Device {
+            device: dev_ptr,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Use this instead of `new` if you don't own a reference to the device,
+    /// e.g. for the device of a stream.
+    /// `dev_ptr` must be valid for the lifetime `'a` of the returned device.
+    pub(crate) fn with_ref(dev_ptr: *mut ffi::SoundIoDevice) -> Self {
+        let dev = Device::new(dev_ptr);
+        dev.inc_ref();
+        dev
     }
 
     /// Add 1 to the reference count of `device`.
-    #[allow(dead_code)]
     fn inc_ref(&self) {
         unsafe { ffi::soundio_device_ref(self.device) }
     }
@@ -401,11 +833,27 @@ impl Device {
         unsafe { ffi::soundio_device_supports_format(self.device, format) == 1u8 }
     }
 
+    /// Returns the first format of `preferred` that the device supports.
+    /// If there is none, the supported format that fits best is chosen:
+    /// native-endian float first, otherwise the format with the highest resolution,
+    /// preferring native endianness if two formats have the same resolution.
+    ///
+    /// Returns `None` if the supported formats are unknown due to a `probe_error`.
+    pub fn negotiate_format(&self,
+                            preferred: &[ffi::enums::SioFormat])
+                            -> Option<ffi::enums::SioFormat> {
+        preferred.iter()
+                 .cloned()
+                 .find(|&format| self.supports_format(format))
+                 .or_else(|| best_format(self.formats()))
+    }
+
     /// Convenience function.
     /// Returns whether `layout` is included in the device's
     /// supported channel layouts.
     pub fn supports_layout(&self, layout: &ChannelLayout) -> bool {
-        unsafe { ffi::soundio_device_supports_layout(self.device, layout.layout) == 1u8 }
+        let raw_layout = layout.to_raw();
+        unsafe { ffi::soundio_device_supports_layout(self.device, &raw_layout) == 1u8 }
     }
 
     /// Convenience function.
@@ -427,7 +875,7 @@ impl Device {
     /// Returns an OutStream struct with default settings.
     /// Sets all fields to defaults.
     /// Returns `ffi::enums::SioError::NoMem` if and only if memory could not be allocated.
-    pub fn create_outstream(&self) -> SioResult<OutStream> {
+    pub fn create_outstream(&self) -> SioResult<OutStream<'a>> {
         let stream_ptr = unsafe { ffi::soundio_outstream_create(self.device) };
         if stream_ptr.is_null() {
             Err(ffi::enums::SioError::NoMem)
@@ -436,6 +884,18 @@ impl Device {
         }
     }
 
+    /// Returns an InStream struct with default settings.
+    /// Sets all fields to defaults.
+    /// Returns `ffi::enums::SioError::NoMem` if and only if memory could not be allocated.
+    pub fn create_instream(&self) -> SioResult<InStream<'a>> {
+        let stream_ptr = unsafe { ffi::soundio_instream_create(self.device) };
+        if stream_ptr.is_null() {
+            Err(ffi::enums::SioError::NoMem)
+        } else {
+            Ok(InStream::new(stream_ptr))
+        }
+    }
+
     /// Returns the number of references on this device.
     pub fn ref_count(&self) -> u32 {
         unsafe { (*self.device).ref_count as u32 }
@@ -457,20 +917,176 @@ impl Device {
             error => Some(error),
         }
     }
+
+    /// Returns a string that uniquely identifies this device.
+    /// If the same physical device supports both input and output,
+    /// there is one `Device` for the input and one for the output.
+    /// In this case, the id of each `Device` will be the same,
+    /// and `aim` will be different.
+    /// Additionally, if the device supports raw mode,
+    /// there may be up to four devices with the same id:
+    /// one for each value of `is_raw` and one for each value of `aim`.
+    pub fn id(&self) -> SioResult<String> {
+        ffi::utils::ptr_to_string(unsafe { (*self.device).id })
+    }
+
+    /// Returns the user-friendly name of the device.
+    /// If the name is not a valid UTF-8 string a `SioError::EncodingString` is returned.
+    pub fn name(&self) -> SioResult<String> {
+        ffi::utils::ptr_to_string(unsafe { (*self.device).name })
+    }
+
+    /// Tells whether this device is an input device or an output device.
+    pub fn aim(&self) -> ffi::enums::SioDeviceAim {
+        unsafe { (*self.device).aim }
+    }
+
+    /// Returns `true` if this is the default device for its `aim`
+    /// in the current device snapshot of the context.
+    pub fn is_default(&self) -> bool {
+        let raw_sio = unsafe { (*self.device).soundio };
+        let default_ptr = unsafe {
+            match self.aim() {
+                ffi::enums::SioDeviceAim::Input => {
+                    match ffi::soundio_default_input_device_index(raw_sio) {
+                        -1 => ptr::null_mut(),
+                        idx => ffi::soundio_get_input_device(raw_sio, idx),
+                    }
+                }
+                ffi::enums::SioDeviceAim::Output => {
+                    match ffi::soundio_default_output_device_index(raw_sio) {
+                        -1 => ptr::null_mut(),
+                        idx => ffi::soundio_get_output_device(raw_sio, idx),
+                    }
+                }
+            }
+        };
+        !default_ptr.is_null() && *self == Device::new(default_ptr)
+    }
+
+    /// Raw means that you are directly opening the hardware device and not
+    /// going through a proxy such as dmix, PulseAudio, or JACK. When you open a
+    /// raw device, other applications on the computer are not able to
+    /// simultaneously access the device. Raw devices do not perform automatic
+    /// resampling and thus tend to have fewer formats available.
+    pub fn is_raw(&self) -> bool {
+        unsafe { (*self.device).is_raw == 1u8 }
+    }
+
+    /// Returns the channel layouts supported by the device.
+    /// The list is empty if the information is missing due to a `probe_error`.
+    pub fn layouts(&self) -> Vec<ChannelLayout> {
+        let (layouts, count) = unsafe { ((*self.device).layouts, (*self.device).layout_count) };
+        if layouts.is_null() {
+            return vec![];
+        }
+        (0..count as isize).map(|idx| ChannelLayout::new(unsafe { layouts.offset(idx) })).collect()
+    }
+
+    /// Returns the current channel layout of the device.
+    /// See `current_format` for details.
+    pub fn current_layout(&self) -> ChannelLayout {
+        ChannelLayout::new(unsafe { &(*self.device).current_layout })
+    }
+
+    /// Returns the formats supported by the device.
+    /// The slice is empty if the information is missing due to a `probe_error`.
+    pub fn formats(&self) -> &[ffi::enums::SioFormat] {
+        let (formats, count) = unsafe { ((*self.device).formats, (*self.device).format_count) };
+        if formats.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(formats, count as usize) }
+        }
+    }
+
+    /// A device is either a raw device or it is a virtual device that is
+    /// provided by a software mixing service such as dmix or PulseAudio (see
+    /// `is_raw`). If it is a raw device, the current format is meaningless;
+    /// the device has no current format until you open it. On the other hand,
+    /// if it is a virtual device, the current format describes the
+    /// destination sample format that your audio will be converted to.
+    ///
+    /// Returns `None` if the current format is unavailable.
+    pub fn current_format(&self) -> Option<ffi::enums::SioFormat> {
+        match unsafe { (*self.device).current_format } {
+            ffi::enums::SioFormat::Invalid => None,
+            fmt => Some(fmt),
+        }
+    }
+
+    /// Returns the ranges of sample rates supported by the device.
+    /// The list is empty if the information is missing due to a `probe_error`.
+    pub fn sample_rates(&self) -> Vec<SampleRateRange> {
+        let (ranges, count) = unsafe {
+            ((*self.device).sample_rates, (*self.device).sample_rate_count)
+        };
+        if ranges.is_null() {
+            return vec![];
+        }
+        unsafe { slice::from_raw_parts(ranges, count as usize) }
+            .iter()
+            .map(|r| {
+                SampleRateRange {
+                    min: r.min as u32,
+                    max: r.max as u32,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the current sample rate of the device or `None`
+    /// if the information is missing due to a `probe_error`.
+    /// See `current_format` for details.
+    pub fn sample_rate_current(&self) -> Option<u32> {
+        match unsafe { (*self.device).sample_rate_current } {
+            0 => None,
+            rate => Some(rate as u32),
+        }
+    }
+
+    /// Software latency minimum in seconds. If this value is unknown or
+    /// irrelevant, it is set to 0.0.
+    /// For PulseAudio and WASAPI this value is unknown until you open a
+    /// stream.
+    pub fn software_latency_min(&self) -> f64 {
+        unsafe { (*self.device).software_latency_min }
+    }
+
+    /// Software latency maximum in seconds. If this value is unknown or
+    /// irrelevant, it is set to 0.0.
+    /// For PulseAudio and WASAPI this value is unknown until you open a
+    /// stream.
+    pub fn software_latency_max(&self) -> f64 {
+        unsafe { (*self.device).software_latency_max }
+    }
+
+    /// Software latency in seconds. If this value is unknown or
+    /// irrelevant, it is set to 0.0.
+    /// For PulseAudio and WASAPI this value is unknown until you open a
+    /// stream.
+    pub fn software_latency_current(&self) -> f64 {
+        unsafe { (*self.device).software_latency_current }
+    }
 }
-impl Display for Device {
+impl<'a> Display for Device<'a> {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
         let str_ptr = unsafe { (*self.device).name };
         write!(f, "{}", ffi::utils::ptr_to_string(str_ptr).unwrap())
     }
 }
-impl Drop for Device {
+impl<'a> Clone for Device<'a> {
+    fn clone(&self) -> Self {
+        Device::with_ref(self.device)
+    }
+}
+impl<'a> Drop for Device<'a> {
     fn drop(&mut self) {
         self.dec_ref()
     }
 }
-impl PartialEq for Device {
-    fn eq(&self, other: &Self) -> bool {
+impl<'a, 'b> PartialEq<Device<'b>> for Device<'a> {
+    fn eq(&self, other: &Device<'b>) -> bool {
         unsafe { ffi::soundio_device_equal(self.device, other.device) == 1u8 }
     }
 }
@@ -0,0 +1,81 @@
+use std::ops::Range;
+
+use ffi;
+use ffi::enums::SioDeviceAim;
+use base::{Device, SioResult, SoundIo};
+
+/// Iterator over the input or output devices of a `SoundIo` context,
+/// returned from `SoundIo::devices`.
+///
+/// The devices are taken from the snapshot of the last `SoundIo::flush_events`.
+/// Use `Device::is_default` to find the default device.
+pub struct Devices<'a> {
+    sio: &'a SoundIo,
+    aim: SioDeviceAim,
+    indices: Range<u32>,
+}
+impl<'a> Iterator for Devices<'a> {
+    type Item = Device<'a>;
+
+    fn next(&mut self) -> Option<Device<'a>> {
+        let idx = self.indices.next()?;
+        // `flush_events` might have replaced the snapshot in the meantime
+        if idx >= device_count(self.sio, self.aim).unwrap_or(0) {
+            self.indices = 0..0;
+            return None;
+        }
+        match self.aim {
+            SioDeviceAim::Input => self.sio.input_device(idx),
+            SioDeviceAim::Output => self.sio.output_device(idx),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.indices.len()))
+    }
+}
+
+fn device_count(sio: &SoundIo, aim: SioDeviceAim) -> Option<u32> {
+    match aim {
+        SioDeviceAim::Input => sio.input_device_count(),
+        SioDeviceAim::Output => sio.output_device_count(),
+    }
+}
+
+impl SoundIo {
+    /// Returns an iterator over the input or output devices, depending on `aim`.
+    ///
+    /// Returns `ffi::enums::SioError::Invalid` if you never called `flush_events`,
+    /// i.e. there is no device snapshot yet.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate rsoundio;
+    ///
+    /// let sio = rsoundio::SoundIo::default();
+    /// sio.connect().unwrap();
+    /// sio.flush_events();
+    /// for dev in sio.output_devices().unwrap() {
+    ///     println!("{}{}", dev, if dev.is_default() { " (default)" } else { "" });
+    /// }
+    /// ```
+    pub fn devices(&self, aim: SioDeviceAim) -> SioResult<Devices<'_>> {
+        let count = try!(device_count(self, aim).ok_or(ffi::enums::SioError::Invalid));
+        Ok(Devices {
+            sio: self,
+            aim: aim,
+            indices: 0..count,
+        })
+    }
+
+    /// Returns an iterator over the input devices, see `devices`.
+    pub fn input_devices(&self) -> SioResult<Devices<'_>> {
+        self.devices(SioDeviceAim::Input)
+    }
+
+    /// Returns an iterator over the output devices, see `devices`.
+    pub fn output_devices(&self) -> SioResult<Devices<'_>> {
+        self.devices(SioDeviceAim::Output)
+    }
+}
@@ -0,0 +1,147 @@
+use std::panic;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+
+use ffi;
+use base::{SioResult, SoundIo};
+
+/// The context of a running event loop, null once it was dropped.
+struct Context(*const SoundIo);
+// `SoundIo::wakeup` only uses the parts of the context that are thread-safe
+unsafe impl Send for Context {}
+
+/// Clears the shared context before the `SoundIo` is dropped, even if the loop panics.
+struct ContextGuard(Arc<Mutex<Context>>);
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        // a poisoned lock still holds a valid context
+        let mut context = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        context.0 = ::std::ptr::null();
+    }
+}
+
+/// A thread that owns a `SoundIo` context, waits for its events and calls
+/// `SoundIo::flush_events` in a loop, so the registered context callbacks
+/// are dispatched on this thread.
+///
+/// The context is created on the thread, as it can't be sent between threads.
+/// Register the callbacks and connect to a backend in the `setup` closure of `EventLoop::spawn`.
+///
+/// # Example
+///
+/// ```
+/// extern crate rsoundio;
+///
+/// let events = rsoundio::EventLoop::spawn(|sio| {
+///                                             sio.register_devices_change_callback(|| {
+///                                                 println!("devices changed")
+///                                             });
+///                                             sio.connect()
+///                                         },
+///                                         |_| {})
+///                  .unwrap();
+/// // ...
+/// events.stop();
+/// ```
+pub struct EventLoop {
+    context: Arc<Mutex<Context>>,
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+impl EventLoop {
+    /// Spawns a thread that creates a `SoundIo` context and passes it to `setup`.
+    /// Afterwards the thread waits for events and calls `on_events` after every
+    /// call to `SoundIo::flush_events`, e.g. to poll a `DeviceWatcher`.
+    ///
+    /// Returns the error of `setup`, or `ffi::enums::SioError::SystemResources`
+    /// if the thread could not be spawned. A panic in `setup` is resumed.
+    pub fn spawn<S, E>(setup: S, mut on_events: E) -> SioResult<EventLoop>
+        where S: FnOnce(&mut SoundIo) -> SioResult<()> + Send + 'static,
+              E: FnMut(&SoundIo) + Send + 'static
+    {
+        let context = Arc::new(Mutex::new(Context(::std::ptr::null())));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel();
+        let (thread_context, thread_stopped) = (context.clone(), stopped.clone());
+        let thread = try!(thread::Builder::new()
+                              .name("rsoundio-events".to_string())
+                              .spawn(move || {
+                                  let mut sio = SoundIo::default();
+                                  if let Err(err) = setup(&mut sio) {
+                                      let _ = tx.send(Err(err));
+                                      return;
+                                  }
+                                  thread_context.lock().unwrap().0 = &sio;
+                                  // dropped before `sio`
+                                  let _guard = ContextGuard(thread_context);
+                                  let _ = tx.send(Ok(()));
+                                  while !thread_stopped.load(Ordering::SeqCst) {
+                                      sio.flush_events();
+                                      on_events(&sio);
+                                      // unlike `wait_events` this doesn't miss the wakeup of `stop`
+                                      sio.wait_signal();
+                                  }
+                              })
+                              .map_err(|_| ffi::enums::SioError::SystemResources));
+        let mut event_loop = EventLoop {
+            context: context,
+            stopped: stopped,
+            thread: Some(thread),
+        };
+        let result = match rx.recv() {
+            Ok(Ok(())) => return Ok(event_loop),
+            Ok(Err(err)) => err,
+            // the thread exited without a result because `setup` panicked
+            Err(_) => ffi::enums::SioError::SystemResources,
+        };
+        match event_loop.join() {
+            Ok(()) => Err(result),
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+
+    /// Returns `false` if the loop stopped, e.g. because of a panic.
+    pub fn is_running(&self) -> bool {
+        let context = self.context.lock().unwrap_or_else(|err| err.into_inner());
+        self.thread.is_some() && !context.0.is_null()
+    }
+
+    /// Stops the loop and waits for the thread to exit.
+    /// The `SoundIo` context is dropped on the thread.
+    ///
+    /// A panic of the thread, e.g. in a context callback or in `on_events`, is resumed.
+    pub fn stop(mut self) {
+        if let Err(payload) = self.join() {
+            panic::resume_unwind(payload)
+        }
+    }
+
+    /// Stops the thread and returns its result.
+    fn join(&mut self) -> thread::Result<()> {
+        match self.thread.take() {
+            Some(thread) => {
+                self.stopped.store(true, Ordering::SeqCst);
+                self.wakeup();
+                thread.join()
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Calls `SoundIo::wakeup` unless the context was already dropped.
+    fn wakeup(&self) {
+        // the lock keeps the thread from dropping the context in the meantime
+        let context = self.context.lock().unwrap_or_else(|err| err.into_inner());
+        if !context.0.is_null() {
+            unsafe { (*context.0).wakeup() }
+        }
+    }
+}
+impl Drop for EventLoop {
+    /// Stops the loop, a panic of the thread is ignored.
+    fn drop(&mut self) {
+        let _ = self.join();
+    }
+}
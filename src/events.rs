@@ -0,0 +1,155 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll, Waker};
+
+use futures::Stream;
+
+use ffi;
+use base::{ContextCallbacks, SoundIo};
+
+/// Wakes the task that waits for events, set from `on_events_signal`.
+#[derive(Default)]
+pub struct Signal {
+    signaled: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+impl Signal {
+    /// Called from an unknown thread when `wait_events` would be woken up.
+    pub fn notify(&self) {
+        self.signaled.store(true, Ordering::SeqCst);
+        // a poisoned lock still holds a valid waker
+        if let Some(waker) = self.waker.lock().unwrap_or_else(|err| err.into_inner()).take() {
+            waker.wake()
+        }
+    }
+
+    fn register(&self, waker: &Waker) {
+        *self.waker.lock().unwrap_or_else(|err| err.into_inner()) = Some(waker.clone());
+    }
+
+    fn take(&self) -> bool {
+        self.signaled.swap(false, Ordering::SeqCst)
+    }
+}
+
+/// An event of a `SoundIo` context, yielded by `SoundIo::events`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SoundIoEvent {
+    /// The list of devices changed, see `SoundIo::register_devices_change_callback`.
+    DevicesChanged,
+    /// The backend disconnected, see `SoundIo::register_backend_disconnect_callback`.
+    BackendDisconnected(ffi::enums::SioError),
+}
+
+/// A `Stream` of `SoundIoEvent`s, returned from `SoundIo::events`.
+///
+/// Polling the stream calls `SoundIo::flush_events`, the task is woken
+/// through the `on_events_signal` callback of libsoundio.
+/// The stream never ends.
+///
+/// Dropping the stream restores the context callbacks that were registered before.
+pub struct Events<'a> {
+    sio: &'a mut SoundIo,
+    queue: Rc<RefCell<VecDeque<SoundIoEvent>>>,
+    previous: Rc<RefCell<Option<ContextCallbacks>>>,
+}
+impl<'a> Drop for Events<'a> {
+    fn drop(&mut self) {
+        // drops the callbacks of the stream, which share the queue
+        if let Some(previous) = self.previous.borrow_mut().take() {
+            self.sio.restore_callbacks(previous);
+        }
+    }
+}
+impl<'a> Stream for Events<'a> {
+    type Item = SoundIoEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<SoundIoEvent>> {
+        if self.queue.borrow().is_empty() {
+            // register before flushing, so a signal in between wakes the task again
+            self.sio.signal().register(cx.waker());
+            self.sio.signal().take();
+            self.sio.flush_events();
+        }
+        match self.queue.borrow_mut().pop_front() {
+            Some(event) => Poll::Ready(Some(event)),
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// A `Future` that resolves when events are ready, returned from `SoundIo::wait_events_async`.
+pub struct WaitEvents<'a> {
+    sio: &'a SoundIo,
+    flushed: bool,
+}
+impl<'a> Future for WaitEvents<'a> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.sio.signal().register(cx.waker());
+        if !self.flushed {
+            self.flushed = true;
+            self.sio.signal().take();
+            self.sio.flush_events();
+            return Poll::Pending;
+        }
+        if self.sio.signal().take() {
+            self.sio.flush_events();
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl SoundIo {
+    /// Returns a `Stream` of the device and backend events of the context,
+    /// which can be used with any async runtime. Requires the `async` feature.
+    ///
+    /// The callbacks registered with `register_devices_change_callback` and
+    /// `register_backend_disconnect_callback` are still called before an event is queued.
+    /// They are restored when the stream is dropped.
+    pub fn events(&mut self) -> Events<'_> {
+        let queue = Rc::new(RefCell::new(VecDeque::new()));
+        let previous = Rc::new(RefCell::new(Some(self.take_callbacks())));
+        let (devices_queue, devices_previous) = (queue.clone(), previous.clone());
+        self.register_devices_change_callback(move || {
+            if let Some(ContextCallbacks { devices_change: Some(ref mut f), .. }) =
+                   *devices_previous.borrow_mut() {
+                f()
+            }
+            devices_queue.borrow_mut().push_back(SoundIoEvent::DevicesChanged)
+        });
+        let (disconnect_queue, disconnect_previous) = (queue.clone(), previous.clone());
+        self.register_backend_disconnect_callback(move |err| {
+            if let Some(ContextCallbacks { backend_disconnect: Some(ref mut f), .. }) =
+                   *disconnect_previous.borrow_mut() {
+                f(err)
+            }
+            disconnect_queue.borrow_mut().push_back(SoundIoEvent::BackendDisconnected(err))
+        });
+        Events {
+            sio: self,
+            queue: queue,
+            previous: previous,
+        }
+    }
+
+    /// The async version of `wait_events`: calls `flush_events` and resolves when
+    /// another event is ready or `wakeup` was called. Requires the `async` feature.
+    ///
+    /// Be ready for spurious wakeups.
+    /// Only one task at a time should wait for the events of a context.
+    pub fn wait_events_async(&self) -> WaitEvents<'_> {
+        WaitEvents {
+            sio: self,
+            flushed: false,
+        }
+    }
+}
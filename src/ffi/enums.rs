@@ -7,7 +7,7 @@ use ffi::utils::*;
 
 /// Possible error codes.
 #[allow(dead_code,non_camel_case_types)]
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u32)]
 pub enum SioError {
     None = 0,
@@ -54,7 +54,7 @@ impl Display for SioError {
 
 /// Specifies where a channel is physically located.
 #[allow(dead_code,non_camel_case_types)]
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 #[repr(u32)]
 pub enum SioChannelId {
     Invalid = 0,
@@ -149,9 +149,9 @@ impl From<String> for SioChannelId {
     }
 }
 
-/// Built-in channel layouts for convenience.
+/// Built-in channel layouts for convenience, see `ChannelLayout::from_id`.
 #[allow(dead_code,non_camel_case_types)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[repr(u32)]
 pub enum SioChannelLayoutId {
     Mono = 0,
@@ -202,7 +202,7 @@ impl Display for SioBackend {
 }
 
 #[allow(dead_code,non_camel_case_types)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u32)]
 pub enum SioDeviceAim {
     /// capture/recording
@@ -213,7 +213,7 @@ pub enum SioDeviceAim {
 
 /// Supported sound formats, each for little- and big-endian.
 #[allow(dead_code,non_camel_case_types)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 #[repr(u32)]
 pub enum SioFormat {
     Invalid = 0,
@@ -229,12 +229,9 @@ pub enum SioFormat {
     U16LE = 5,
     /// Unsigned 16 bit big-endian
     U16BE = 6,
-    /// The 24 bit types are not supported in rsoundio at the moment.
-    /// Because there is no native number type that is 24 bits wide,
-    /// (at least not in Rusts `std`) I had to use something like
-    /// C unions in Rust to support them.
-    /// This would probably be unsafe and working with
-    /// 3 byte number types is also not too much fun.
+    /// The 24 bit formats are written and read as `I24` and `U24` samples.
+    /// Depending on `bytes_per_sample` they are either packed into 3 bytes
+    /// or stored in the lower 3 bytes of a 4 byte container.
     ///
     /// Signed 24 bit little-endian
     S24LE = 7,
@@ -273,6 +270,3 @@ impl SioFormat {
         unsafe { soundio_get_bytes_per_sample(self) as i32 }
     }
 }
-
-// #[allow(dead_code,non_camel_case_types)]
-// enum SoundIoRingBuffer { }
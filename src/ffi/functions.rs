@@ -346,7 +346,7 @@ extern "C" {
     /// * #SoundIoErrorNoSuchClient
     /// * #SoundIoErrorIncompatibleBackend
     /// * #SoundIoErrorIncompatibleDevice
-    pub fn soundio_instream_open(instream: *mut SoundIoInStream) -> c_int;
+    pub fn soundio_instream_open(instream: *mut SoundIoInStream) -> SioError;
     /// After you call this function, SoundIoInStream::read_callback will be called.
     ///
     /// Possible errors:
@@ -354,7 +354,7 @@ extern "C" {
     /// * #SoundIoErrorStreaming
     /// * #SoundIoErrorOpeningDevice
     /// * #SoundIoErrorSystemResources
-    pub fn soundio_instream_start(instream: *mut SoundIoInStream) -> c_int;
+    pub fn soundio_instream_start(instream: *mut SoundIoInStream) -> SioError;
     /// Call this function when you are ready to begin reading from the device
     /// buffer.
     /// * `instream` - (in) The input stream you want to read from.
@@ -387,7 +387,7 @@ extern "C" {
     pub fn soundio_instream_begin_read(instream: *mut SoundIoInStream,
                                        areas: *mut *mut SoundIoChannelArea,
                                        frame_count: *mut c_int)
-                                       -> c_int;
+                                       -> SioError;
     /// This will drop all of the frames from when you called
     /// ::soundio_instream_begin_read.
     /// You must call this function only from the SoundIoInStream::read_callback thread context.
@@ -396,7 +396,7 @@ extern "C" {
     ///
     /// Possible errors:
     /// * #SoundIoErrorStreaming
-    pub fn soundio_instream_end_read(instream: *mut SoundIoInStream) -> c_int;
+    pub fn soundio_instream_end_read(instream: *mut SoundIoInStream) -> SioError;
     /// If the underyling device supports pausing, this pauses the stream and
     /// prevents SoundIoInStream::read_callback from being called. Otherwise this returns
     /// #SoundIoErrorIncompatibleDevice.
@@ -408,7 +408,7 @@ extern "C" {
     /// * #SoundIoErrorBackendDisconnected
     /// * #SoundIoErrorStreaming
     /// * #SoundIoErrorIncompatibleDevice - device does not support pausing/unpausing
-    pub fn soundio_instream_pause(instream: *mut SoundIoInStream, pause: u8) -> c_int;
+    pub fn soundio_instream_pause(instream: *mut SoundIoInStream, pause: u8) -> SioError;
     /// Obtain the number of seconds that the next frame of sound being
     /// captured will take to arrive in the buffer, plus the amount of time that is
     /// represented in the buffer. This includes both software and hardware latency.
@@ -419,5 +419,35 @@ extern "C" {
     /// * #SoundIoErrorStreaming
     pub fn soundio_instream_get_latency(instream: *mut SoundIoInStream,
                                         out_latency: *mut c_double)
-                                        -> c_int;
+                                        -> SioError;
+    /// A ring buffer is a single-reader single-writer lock-free fixed-size queue.
+    /// libsoundio ring buffers use memory mapping techniques to enable a
+    /// contiguous buffer when reading or writing across the boundary of the ring
+    /// buffer's capacity.
+    /// `requested_capacity` in bytes.
+    /// Returns `NULL` if and only if memory could not be allocated.
+    /// Use ::soundio_ring_buffer_capacity to get the actual capacity, which might
+    /// be greater for alignment purposes.
+    /// See also ::soundio_ring_buffer_destroy
+    pub fn soundio_ring_buffer_create(soundio: *mut SoundIo,
+                                      requested_capacity: c_int)
+                                      -> *mut SoundIoRingBuffer;
+    pub fn soundio_ring_buffer_destroy(ring_buffer: *mut SoundIoRingBuffer);
+    /// When you create a ring buffer, capacity might be more than the requested
+    /// capacity for alignment purposes. This function returns the actual capacity.
+    pub fn soundio_ring_buffer_capacity(ring_buffer: *mut SoundIoRingBuffer) -> c_int;
+    /// Do not write more than capacity.
+    pub fn soundio_ring_buffer_write_ptr(ring_buffer: *mut SoundIoRingBuffer) -> *mut c_char;
+    /// `count` in bytes.
+    pub fn soundio_ring_buffer_advance_write_ptr(ring_buffer: *mut SoundIoRingBuffer,
+                                                 count: c_int);
+    /// Do not read more than capacity.
+    pub fn soundio_ring_buffer_read_ptr(ring_buffer: *mut SoundIoRingBuffer) -> *mut c_char;
+    /// `count` in bytes.
+    pub fn soundio_ring_buffer_advance_read_ptr(ring_buffer: *mut SoundIoRingBuffer,
+                                                count: c_int);
+    /// Returns how many bytes of the buffer is used, ready for reading.
+    pub fn soundio_ring_buffer_fill_count(ring_buffer: *mut SoundIoRingBuffer) -> c_int;
+    /// Returns how many bytes of the buffer is free, ready for writing.
+    pub fn soundio_ring_buffer_free_count(ring_buffer: *mut SoundIoRingBuffer) -> c_int;
 }
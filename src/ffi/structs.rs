@@ -54,17 +54,17 @@ pub struct SoundIo {
     /// * #SoundIoErrorOpeningDevice - unexpected problem accessing device
     ///   information
     /// ::soundio_flush_events or ::soundio_wait_events.
-    pub on_devices_change: Option<unsafe extern "C" fn(arg1: *mut SoundIo)>,
+    pub on_devices_change: Option<extern "C" fn(arg1: *mut SoundIo)>,
     /// Optional callback. Called when the backend disconnects.
     /// For example, when the JACK server shuts down. When this
     /// happens, listing devices and opening streams will always
     /// fail with SoundIoErrorBackendDisconnected.
-    pub on_backend_disconnect: Option<unsafe extern "C" fn(arg1: *mut SoundIo, err: c_int)>,
+    pub on_backend_disconnect: Option<extern "C" fn(arg1: *mut SoundIo, err: SioError)>,
     /// Optional callback. Called from an unknown thread that
     /// you should not use to call any soundio functions.
     /// You may use this to signal a condition variable to wake up.
     /// Called when ::soundio_wait_events would be woken up.
-    pub on_events_signal: Option<unsafe extern "C" fn(arg1: *mut SoundIo)>,
+    pub on_events_signal: Option<extern "C" fn(arg1: *mut SoundIo)>,
     /// Read-only. After calling ::soundio_connect or
     /// ::soundio_connect_backend, this field tells which
     /// backend is currently connected.
@@ -295,7 +295,7 @@ pub struct SoundIoOutStream {
     /// If setting the channel layout fails for some reason, this field is set
     /// to an error code. Possible error codes are:
     /// * #SoundIoErrorIncompatibleDevice
-    pub layout_error: c_int,
+    pub layout_error: SioError,
 }
 
 /// Represents an audio input stream.
@@ -340,21 +340,21 @@ pub struct SoundIoInStream {
     /// for a long time. This includes all I/O functions (disk, TTY, network),
     /// malloc, free, printf, pthread_mutex_lock, sleep, wait, poll, select,
     /// pthread_join, pthread_cond_wait, etc.
-    pub read_callback: Option<unsafe extern "C" fn(arg1: *mut SoundIoInStream,
-                                                       frame_count_min: c_int,
-                                                       frame_count_max: c_int)>,
+    pub read_callback: Option<extern "C" fn(arg1: *mut SoundIoInStream,
+                                                frame_count_min: c_int,
+                                                frame_count_max: c_int)>,
     /// This optional callback happens when the sound device buffer is full,
     /// yet there is more captured audio to put in it.
     /// This is never fired for PulseAudio.
     /// This is called from the SoundIoInStream::read_callback thread context.
-    pub overflow_callback: Option<unsafe extern "C" fn(arg1: *mut SoundIoInStream)>,
+    pub overflow_callback: Option<extern "C" fn(arg1: *mut SoundIoInStream)>,
     /// Optional callback. `err` is always SoundIoErrorStreaming.
     /// SoundIoErrorStreaming is an unrecoverable error. The stream is in an
     /// invalid state and must be destroyed.
     /// If you do not supply `error_callback`, the default callback will print
     /// a message to stderr and then abort().
     /// This is called from the SoundIoInStream::read_callback thread context.
-    pub error_callback: Option<unsafe extern "C" fn(arg1: *mut SoundIoInStream, err: c_int)>,
+    pub error_callback: Option<extern "C" fn(arg1: *mut SoundIoInStream, err: SioError)>,
     /// Optional: Name of the stream. Defaults to "SoundIoInStream";
     /// PulseAudio uses this for the stream name.
     /// JACK uses this for the client name of the client that connects when you
@@ -374,3 +374,7 @@ pub struct SoundIoInStream {
     /// to an error code. Possible error codes are: #SoundIoErrorIncompatibleDevice
     pub layout_error: SioError,
 }
+
+/// Opaque handle of a libsoundio ring buffer.
+/// Only ever used behind a pointer.
+pub enum SoundIoRingBuffer {}
@@ -25,10 +25,37 @@
 //! // loop { sio.wait_events(); }
 //! ```
 
+#[cfg(feature = "async")]
+extern crate futures;
+#[cfg(feature = "regex")]
+extern crate regex;
+
 mod ffi;
 mod base;
 mod stream;
+mod ring_buffer;
+mod area;
+mod sample;
+mod mixer;
+mod ambisonics;
+mod stereo;
+mod watcher;
+mod event_loop;
+mod devices;
+mod query;
+#[cfg(feature = "async")]
+mod events;
 
 pub use ffi::enums::*;
 pub use base::*;
 pub use stream::*;
+pub use ring_buffer::*;
+pub use area::*;
+pub use sample::{Sample, I24, U24};
+pub use mixer::*;
+pub use watcher::*;
+pub use event_loop::*;
+pub use devices::*;
+pub use query::*;
+#[cfg(feature = "async")]
+pub use events::{Events, SoundIoEvent, WaitEvents};
@@ -0,0 +1,347 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use ffi;
+use ffi::enums::SioChannelId;
+use base::{ChannelLayout, SioResult};
+
+/// Gains used by `ChannelMixer` if a channel of the source layout
+/// is missing in the target layout and has to be folded into other channels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MixOptions {
+    /// Gain of a center channel that is folded into front left and right.
+    pub center_gain: f32,
+    /// Gain of a side or back channel that is folded into the front channels.
+    pub surround_gain: f32,
+    /// Gain of a LFE channel that is folded into the main channels, `0.0` drops it.
+    pub lfe_gain: f32,
+    /// Scales the matrix down so that no output channel can clip.
+    pub normalize: bool,
+}
+impl Default for MixOptions {
+    fn default() -> Self {
+        MixOptions {
+            center_gain: FRAC_1_SQRT_2,
+            surround_gain: FRAC_1_SQRT_2,
+            lfe_gain: 0.0,
+            normalize: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Fold {
+    /// The channel has an equivalent in the target layout.
+    Unity,
+    /// The channel is attenuated by 3 dB, e.g. because it is spread across two channels.
+    Spread,
+    Center,
+    Surround,
+    /// A surround channel folded into the front center.
+    SurroundCenter,
+    Lfe,
+}
+
+const FRONT_LEFT: &[(&[SioChannelId], Fold)] =
+    &[(&[SioChannelId::FrontLeft], Fold::Unity), (&[SioChannelId::FrontCenter], Fold::Spread)];
+const FRONT_RIGHT: &[(&[SioChannelId], Fold)] =
+    &[(&[SioChannelId::FrontRight], Fold::Unity), (&[SioChannelId::FrontCenter], Fold::Spread)];
+const FRONT_CENTER: &[(&[SioChannelId], Fold)] =
+    &[(&[SioChannelId::FrontCenter], Fold::Unity),
+      (&[SioChannelId::FrontLeft, SioChannelId::FrontRight], Fold::Center)];
+const SIDE_LEFT: &[(&[SioChannelId], Fold)] =
+    &[(&[SioChannelId::SideLeft], Fold::Unity),
+      (&[SioChannelId::BackLeft], Fold::Unity),
+      (&[SioChannelId::FrontLeft], Fold::Surround),
+      (&[SioChannelId::FrontCenter], Fold::SurroundCenter)];
+const SIDE_RIGHT: &[(&[SioChannelId], Fold)] =
+    &[(&[SioChannelId::SideRight], Fold::Unity),
+      (&[SioChannelId::BackRight], Fold::Unity),
+      (&[SioChannelId::FrontRight], Fold::Surround),
+      (&[SioChannelId::FrontCenter], Fold::SurroundCenter)];
+const BACK_LEFT: &[(&[SioChannelId], Fold)] =
+    &[(&[SioChannelId::BackLeft], Fold::Unity),
+      (&[SioChannelId::SideLeft], Fold::Unity),
+      (&[SioChannelId::FrontLeft], Fold::Surround),
+      (&[SioChannelId::FrontCenter], Fold::SurroundCenter)];
+const BACK_RIGHT: &[(&[SioChannelId], Fold)] =
+    &[(&[SioChannelId::BackRight], Fold::Unity),
+      (&[SioChannelId::SideRight], Fold::Unity),
+      (&[SioChannelId::FrontRight], Fold::Surround),
+      (&[SioChannelId::FrontCenter], Fold::SurroundCenter)];
+const BACK_CENTER: &[(&[SioChannelId], Fold)] =
+    &[(&[SioChannelId::BackCenter], Fold::Unity),
+      (&[SioChannelId::BackLeft, SioChannelId::BackRight], Fold::Spread),
+      (&[SioChannelId::SideLeft, SioChannelId::SideRight], Fold::Spread),
+      (&[SioChannelId::FrontLeft, SioChannelId::FrontRight], Fold::SurroundCenter),
+      (&[SioChannelId::FrontCenter], Fold::SurroundCenter)];
+const LFE: &[(&[SioChannelId], Fold)] =
+    &[(&[SioChannelId::Lfe], Fold::Unity),
+      (&[SioChannelId::FrontLeft, SioChannelId::FrontRight], Fold::Lfe),
+      (&[SioChannelId::FrontCenter], Fold::Lfe)];
+
+/// Returns the channels `channel` is mixed into, in the order of preference.
+fn fold_targets(channel: SioChannelId) -> &'static [(&'static [SioChannelId], Fold)] {
+    match channel {
+        SioChannelId::FrontLeft |
+        SioChannelId::FrontLeftCenter |
+        SioChannelId::FrontLeftWide |
+        SioChannelId::FrontLeftHigh |
+        SioChannelId::TopFrontLeft |
+        SioChannelId::TopFrontLeftCenter => FRONT_LEFT,
+        SioChannelId::FrontRight |
+        SioChannelId::FrontRightCenter |
+        SioChannelId::FrontRightWide |
+        SioChannelId::FrontRightHigh |
+        SioChannelId::TopFrontRight |
+        SioChannelId::TopFrontRightCenter => FRONT_RIGHT,
+        SioChannelId::FrontCenter |
+        SioChannelId::FrontCenterHigh |
+        SioChannelId::TopFrontCenter |
+        SioChannelId::TopCenter => FRONT_CENTER,
+        SioChannelId::SideLeft | SioChannelId::TopSideLeft => SIDE_LEFT,
+        SioChannelId::SideRight | SioChannelId::TopSideRight => SIDE_RIGHT,
+        SioChannelId::BackLeft | SioChannelId::BackLeftCenter | SioChannelId::TopBackLeft => {
+            BACK_LEFT
+        }
+        SioChannelId::BackRight | SioChannelId::BackRightCenter | SioChannelId::TopBackRight => {
+            BACK_RIGHT
+        }
+        SioChannelId::BackCenter | SioChannelId::TopBackCenter => BACK_CENTER,
+        SioChannelId::Lfe | SioChannelId::LeftLfe | SioChannelId::RightLfe | SioChannelId::Lfe2 => {
+            LFE
+        }
+        _ => &[],
+    }
+}
+
+/// Mixes frames of one channel layout into another one, e.g. 5.1 content to stereo.
+///
+/// The mixer is a matrix with a gain for every pair of input and output channel.
+/// A channel is copied if the target layout contains it,
+/// otherwise it is folded into the nearest channels, see `MixOptions`.
+/// Channels without a position, e.g. `SioChannelId::Aux0`, are only copied.
+///
+/// The matrix is allocated once, mixing doesn't allocate
+/// and can be done within `OutStream::write_callback`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelMixer {
+    input_count: usize,
+    output_count: usize,
+    // row major, one row per output channel
+    gains: Vec<f32>,
+}
+impl ChannelMixer {
+    /// Creates a mixer from `input` to `output` with the default `MixOptions`.
+    pub fn new(input: &ChannelLayout, output: &ChannelLayout) -> ChannelMixer {
+        ChannelMixer::with_options(input, output, MixOptions::default())
+    }
+
+    /// Creates a mixer from `input` to `output`.
+    pub fn with_options(input: &ChannelLayout,
+                        output: &ChannelLayout,
+                        options: MixOptions)
+                        -> ChannelMixer {
+        let mut mixer = ChannelMixer::silent(input.channel_count() as usize,
+                                             output.channel_count() as usize);
+        for (in_idx, &channel) in input.channels().iter().enumerate() {
+            if let Some(out_idx) = output.find_channel(channel) {
+                mixer.set_gain(in_idx, out_idx as usize, 1.0);
+                continue;
+            }
+            let target = fold_targets(channel).iter().find(|&&(channels, _)| {
+                channels.iter().all(|&c| output.find_channel(c).is_some())
+            });
+            if let Some(&(channels, fold)) = target {
+                let gain = match fold {
+                    Fold::Unity => 1.0,
+                    Fold::Spread => FRAC_1_SQRT_2,
+                    Fold::Center => options.center_gain,
+                    Fold::Surround => options.surround_gain,
+                    Fold::SurroundCenter => options.surround_gain * FRAC_1_SQRT_2,
+                    Fold::Lfe if channels.len() > 1 => options.lfe_gain * FRAC_1_SQRT_2,
+                    Fold::Lfe => options.lfe_gain,
+                };
+                for &c in channels {
+                    let out_idx = output.find_channel(c).unwrap() as usize;
+                    let gain = mixer.gain(in_idx, out_idx) + gain;
+                    mixer.set_gain(in_idx, out_idx, gain);
+                }
+            }
+        }
+        if options.normalize {
+            mixer.normalize();
+        }
+        mixer
+    }
+
+    /// Creates a mixer from `input_count` to `output_count` channels with all gains set to zero.
+    pub fn silent(input_count: usize, output_count: usize) -> ChannelMixer {
+        ChannelMixer {
+            input_count: input_count,
+            output_count: output_count,
+            gains: vec![0.0; input_count * output_count],
+        }
+    }
+
+    /// Returns the number of input channels.
+    pub fn input_count(&self) -> usize {
+        self.input_count
+    }
+
+    /// Returns the number of output channels.
+    pub fn output_count(&self) -> usize {
+        self.output_count
+    }
+
+    /// Returns the gain from the `input` to the `output` channel.
+    ///
+    /// Panics if one of the indices is out of bounds.
+    pub fn gain(&self, input: usize, output: usize) -> f32 {
+        self.gains[self.index(input, output)]
+    }
+
+    /// Sets the gain from the `input` to the `output` channel.
+    ///
+    /// Panics if one of the indices is out of bounds.
+    pub fn set_gain(&mut self, input: usize, output: usize, gain: f32) {
+        let idx = self.index(input, output);
+        self.gains[idx] = gain;
+    }
+
+    /// Scales all gains down so that the gains of no output channel sum up to more than `1.0`.
+    pub fn normalize(&mut self) {
+        let max = self.gains
+                      .chunks(self.input_count.max(1))
+                      .map(|row| row.iter().map(|gain| gain.abs()).sum::<f32>())
+                      .fold(0.0f32, f32::max);
+        if max > 1.0 {
+            for gain in &mut self.gains {
+                *gain /= max;
+            }
+        }
+    }
+
+    /// Mixes a single frame with `input_count` samples into
+    /// `output`, which must hold at least `output_count` samples.
+    ///
+    /// Missing input samples are treated as silence.
+    pub fn mix(&self, input: &[f32], output: &mut [f32]) {
+        for (out_idx, out) in output.iter_mut().take(self.output_count).enumerate() {
+            let row = &self.gains[out_idx * self.input_count..(out_idx + 1) * self.input_count];
+            *out = row.iter().zip(input.iter()).map(|(gain, sample)| gain * sample).sum();
+        }
+    }
+
+    /// Mixes interleaved `input` frames into interleaved `output` frames
+    /// and returns the number of mixed frames.
+    pub fn mix_interleaved(&self, input: &[f32], output: &mut [f32]) -> usize {
+        if self.input_count == 0 || self.output_count == 0 {
+            return 0;
+        }
+        let mut frames = 0;
+        for (in_frame, out_frame) in input.chunks(self.input_count)
+                                          .zip(output.chunks_mut(self.output_count)) {
+            if in_frame.len() < self.input_count || out_frame.len() < self.output_count {
+                break;
+            }
+            self.mix(in_frame, out_frame);
+            frames += 1;
+        }
+        frames
+    }
+
+    fn index(&self, input: usize, output: usize) -> usize {
+        assert!(input < self.input_count && output < self.output_count,
+                "channel out of bounds: the mixer maps {} to {} channels but the channels are \
+                 {} and {}",
+                self.input_count,
+                self.output_count,
+                input,
+                output);
+        output * self.input_count + input
+    }
+}
+
+/// The destination of a route, a channel of the layout given by index or by id.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Destination {
+    Index(usize),
+    Channel(SioChannelId),
+}
+impl From<usize> for Destination {
+    fn from(idx: usize) -> Self {
+        Destination::Index(idx)
+    }
+}
+impl From<SioChannelId> for Destination {
+    fn from(channel: SioChannelId) -> Self {
+        Destination::Channel(channel)
+    }
+}
+
+/// Routes source channels to specific channels of a layout, each route with its own gain.
+/// Source channels without a route are dropped.
+///
+/// ```
+/// # use rsoundio::{ChannelLayout, ChannelRouting, SioChannelId, SioChannelLayoutId};
+/// let layout = ChannelLayout::from_id(SioChannelLayoutId::FivePointOne);
+/// let mut routing = ChannelRouting::new(2, &layout);
+/// // the first source channel goes to both front channels
+/// routing.route(0, SioChannelId::FrontLeft, 0.5).unwrap();
+/// routing.route(0, 1, 0.5).unwrap();
+/// // the second one is a click track on the LFE channel
+/// routing.route(1, SioChannelId::Lfe, 1.0).unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelRouting {
+    layout: ChannelLayout,
+    mixer: ChannelMixer,
+}
+impl ChannelRouting {
+    /// Creates a routing from `source_count` channels to `layout` without any routes.
+    pub fn new(source_count: usize, layout: &ChannelLayout) -> ChannelRouting {
+        ChannelRouting {
+            layout: layout.clone(),
+            mixer: ChannelMixer::silent(source_count, layout.channel_count() as usize),
+        }
+    }
+
+    /// Routes the `source` channel to `destination` with `gain`,
+    /// an existing route between both channels is replaced.
+    /// A `gain` of `0.0` removes the route.
+    ///
+    /// Returns `ffi::enums::SioError::Invalid` if the `source` channel doesn't exist
+    /// or the layout doesn't contain the `destination`.
+    pub fn route<D: Into<Destination>>(&mut self,
+                                       source: usize,
+                                       destination: D,
+                                       gain: f32)
+                                       -> SioResult<()> {
+        let output = match destination.into() {
+            Destination::Index(idx) => idx,
+            Destination::Channel(channel) => {
+                try!(self.layout.find_channel(channel).ok_or(ffi::enums::SioError::Invalid)) as usize
+            }
+        };
+        if source >= self.mixer.input_count() || output >= self.mixer.output_count() {
+            return Err(ffi::enums::SioError::Invalid);
+        }
+        self.mixer.set_gain(source, output, gain);
+        Ok(())
+    }
+
+    /// Removes all routes.
+    pub fn clear(&mut self) {
+        self.mixer = ChannelMixer::silent(self.mixer.input_count(), self.mixer.output_count());
+    }
+
+    /// Returns the layout the channels are routed to.
+    pub fn layout(&self) -> &ChannelLayout {
+        &self.layout
+    }
+
+    /// Returns the mixer that applies the routes.
+    pub fn mixer(&self) -> &ChannelMixer {
+        &self.mixer
+    }
+}
@@ -0,0 +1,170 @@
+#[cfg(feature = "regex")]
+use regex::Regex;
+
+use ffi;
+use ffi::enums::{SioDeviceAim, SioFormat};
+use base::{Device, SioResult, SoundIo};
+
+#[derive(Clone, Debug)]
+enum NamePattern {
+    /// Lower case substring.
+    Contains(String),
+    #[cfg(feature = "regex")]
+    Regex(Regex),
+}
+
+/// Selects a device by its properties instead of its index,
+/// which changes e.g. when a USB interface is replugged.
+/// All given conditions have to match.
+///
+/// # Example
+///
+/// ```
+/// extern crate rsoundio;
+///
+/// let sio = rsoundio::SoundIo::default();
+/// sio.connect().unwrap();
+/// sio.flush_events();
+/// let query = rsoundio::DeviceQuery::new()
+///                 .name_contains("focusrite")
+///                 .raw(false)
+///                 .sample_rate(48_000);
+/// if let Ok(dev) = sio.find_output_device(&query) {
+///     println!("{}", dev);
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct DeviceQuery {
+    id: Option<String>,
+    name: Option<NamePattern>,
+    raw: Option<bool>,
+    format: Option<SioFormat>,
+    sample_rate: Option<u32>,
+    channel_count: Option<u32>,
+}
+impl DeviceQuery {
+    /// Creates a query that matches every device.
+    pub fn new() -> DeviceQuery {
+        DeviceQuery::default()
+    }
+
+    /// Matches the device with exactly this `Device::id`.
+    pub fn id<S: Into<String>>(mut self, id: S) -> DeviceQuery {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Matches devices whose name contains `pattern`, ignoring case.
+    pub fn name_contains<S: Into<String>>(mut self, pattern: S) -> DeviceQuery {
+        self.name = Some(NamePattern::Contains(pattern.into().to_lowercase()));
+        self
+    }
+
+    /// Matches devices whose name matches `regex`. Requires the `regex` feature.
+    #[cfg(feature = "regex")]
+    pub fn name_regex(mut self, regex: Regex) -> DeviceQuery {
+        self.name = Some(NamePattern::Regex(regex));
+        self
+    }
+
+    /// Matches raw or non-raw devices, see `Device::is_raw`.
+    pub fn raw(mut self, raw: bool) -> DeviceQuery {
+        self.raw = Some(raw);
+        self
+    }
+
+    /// Matches devices that support `format`.
+    pub fn format(mut self, format: SioFormat) -> DeviceQuery {
+        self.format = Some(format);
+        self
+    }
+
+    /// Matches devices that support `sample_rate`.
+    pub fn sample_rate(mut self, sample_rate: u32) -> DeviceQuery {
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
+    /// Matches devices that support a channel layout with `channel_count` channels.
+    pub fn channel_count(mut self, channel_count: u32) -> DeviceQuery {
+        self.channel_count = Some(channel_count);
+        self
+    }
+
+    /// Returns `true` if `device` fulfills all conditions of the query.
+    ///
+    /// Devices with a `probe_error` might not match the capabilities,
+    /// as the information about them is missing.
+    pub fn matches(&self, device: &Device) -> bool {
+        if let Some(ref id) = self.id {
+            if device.id().ok().as_ref() != Some(id) {
+                return false;
+            }
+        }
+        if let Some(ref pattern) = self.name {
+            let name = match device.name() {
+                Ok(name) => name,
+                Err(_) => return false,
+            };
+            let found = match *pattern {
+                NamePattern::Contains(ref pattern) => name.to_lowercase().contains(pattern.as_str()),
+                #[cfg(feature = "regex")]
+                NamePattern::Regex(ref regex) => regex.is_match(&name),
+            };
+            if !found {
+                return false;
+            }
+        }
+        if let Some(raw) = self.raw {
+            if device.is_raw() != raw {
+                return false;
+            }
+        }
+        if let Some(format) = self.format {
+            if !device.supports_format(format) {
+                return false;
+            }
+        }
+        if let Some(sample_rate) = self.sample_rate {
+            if !device.supports_sample_rate(sample_rate) {
+                return false;
+            }
+        }
+        match self.channel_count {
+            Some(count) => device.layouts().iter().any(|layout| layout.channel_count() == count),
+            None => true,
+        }
+    }
+}
+
+impl SoundIo {
+    /// Returns the input or output device, depending on `aim`, that matches `query`.
+    /// If there are several, the default device is preferred, otherwise the first one is returned.
+    ///
+    /// Possible errors:
+    ///
+    /// - `ffi::enums::SioError::Invalid` - you never called `flush_events`
+    /// - `ffi::enums::SioError::NoSuchDevice` - no device matches
+    pub fn find_device(&self, aim: SioDeviceAim, query: &DeviceQuery) -> SioResult<Device<'_>> {
+        let mut found = None;
+        for dev in try!(self.devices(aim)).filter(|dev| query.matches(dev)) {
+            if dev.is_default() {
+                return Ok(dev);
+            }
+            if found.is_none() {
+                found = Some(dev);
+            }
+        }
+        found.ok_or(ffi::enums::SioError::NoSuchDevice)
+    }
+
+    /// Returns the input device that matches `query`, see `find_device`.
+    pub fn find_input_device(&self, query: &DeviceQuery) -> SioResult<Device<'_>> {
+        self.find_device(SioDeviceAim::Input, query)
+    }
+
+    /// Returns the output device that matches `query`, see `find_device`.
+    pub fn find_output_device(&self, query: &DeviceQuery) -> SioResult<Device<'_>> {
+        self.find_device(SioDeviceAim::Output, query)
+    }
+}
@@ -0,0 +1,145 @@
+use std::cell::Cell;
+use std::os::raw::c_int;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::{cmp, mem, ptr};
+
+use ffi;
+
+/// Owns the libsoundio ring buffer and destroys it
+/// as soon as both the `Producer` and the `Consumer` are dropped.
+struct RawRingBuffer {
+    buffer: *mut ffi::SoundIoRingBuffer,
+}
+// The count and capacity functions of libsoundio may be called from any thread,
+// the pointers are only advanced by the single `Producer` and `Consumer`.
+unsafe impl Send for RawRingBuffer {}
+unsafe impl Sync for RawRingBuffer {}
+impl Drop for RawRingBuffer {
+    fn drop(&mut self) {
+        unsafe { ffi::soundio_ring_buffer_destroy(self.buffer) }
+    }
+}
+
+/// A single-reader single-writer lock-free fixed-size queue
+/// of elements of type `T`, returned from `SoundIo::create_ring_buffer`.
+///
+/// Use `split` to get the `Producer` and `Consumer` halves,
+/// which can be moved into different threads, e.g. an input
+/// and an output stream callback.
+pub struct RingBuffer<T: Copy> {
+    raw: Arc<RawRingBuffer>,
+    phantom: PhantomData<T>,
+}
+impl<T: Copy> RingBuffer<T> {
+    pub(crate) fn new(raw_buffer: *mut ffi::SoundIoRingBuffer) -> Self {
+        RingBuffer {
+            raw: Arc::new(RawRingBuffer { buffer: raw_buffer }),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the number of elements the ring buffer can hold.
+    /// This might be more than the requested capacity for alignment purposes.
+    pub fn capacity(&self) -> usize {
+        capacity::<T>(&self.raw)
+    }
+
+    /// Splits the ring buffer into its writing and its reading half.
+    pub fn split(self) -> (Producer<T>, Consumer<T>) {
+        (Producer {
+            raw: self.raw.clone(),
+            phantom: PhantomData,
+        },
+         Consumer {
+            raw: self.raw,
+            phantom: PhantomData,
+        })
+    }
+}
+
+/// The writing half of a `RingBuffer`.
+pub struct Producer<T: Copy> {
+    raw: Arc<RawRingBuffer>,
+    // `Send` but not `Sync`, so only one thread advances the write pointer
+    phantom: PhantomData<Cell<T>>,
+}
+impl<T: Copy> Producer<T> {
+    /// Writes as many elements of `data` as there is free space for.
+    /// Returns the number of written elements.
+    /// Never blocks, so it is safe to call from a realtime callback.
+    pub fn write(&self, data: &[T]) -> usize {
+        let count = cmp::min(self.free_count(), data.len());
+        if count == 0 {
+            return 0;
+        }
+        let bytes = count * mem::size_of::<T>();
+        unsafe {
+            let dst = ffi::soundio_ring_buffer_write_ptr(self.raw.buffer) as *mut u8;
+            ptr::copy_nonoverlapping(data.as_ptr() as *const u8, dst, bytes);
+            ffi::soundio_ring_buffer_advance_write_ptr(self.raw.buffer, bytes as c_int);
+        }
+        count
+    }
+
+    /// Returns the number of elements that can be written.
+    pub fn free_count(&self) -> usize {
+        let bytes = unsafe { ffi::soundio_ring_buffer_free_count(self.raw.buffer) };
+        bytes as usize / mem::size_of::<T>()
+    }
+
+    /// Returns the number of elements the ring buffer can hold.
+    pub fn capacity(&self) -> usize {
+        capacity::<T>(&self.raw)
+    }
+}
+
+/// The reading half of a `RingBuffer`.
+pub struct Consumer<T: Copy> {
+    raw: Arc<RawRingBuffer>,
+    // `Send` but not `Sync`, so only one thread advances the read pointer
+    phantom: PhantomData<Cell<T>>,
+}
+impl<T: Copy> Consumer<T> {
+    /// Reads as many elements into `data` as are available.
+    /// Returns the number of read elements.
+    /// Never blocks, so it is safe to call from a realtime callback.
+    pub fn read(&self, data: &mut [T]) -> usize {
+        let count = cmp::min(self.fill_count(), data.len());
+        if count == 0 {
+            return 0;
+        }
+        let bytes = count * mem::size_of::<T>();
+        unsafe {
+            let src = ffi::soundio_ring_buffer_read_ptr(self.raw.buffer) as *const u8;
+            ptr::copy_nonoverlapping(src, data.as_mut_ptr() as *mut u8, bytes);
+            ffi::soundio_ring_buffer_advance_read_ptr(self.raw.buffer, bytes as c_int);
+        }
+        count
+    }
+
+    /// Returns the number of elements that are ready for reading.
+    pub fn fill_count(&self) -> usize {
+        let bytes = unsafe { ffi::soundio_ring_buffer_fill_count(self.raw.buffer) };
+        bytes as usize / mem::size_of::<T>()
+    }
+
+    /// Discards all elements that are ready for reading.
+    /// Returns the number of discarded elements.
+    pub fn clear(&self) -> usize {
+        let count = self.fill_count();
+        let bytes = count * mem::size_of::<T>();
+        unsafe { ffi::soundio_ring_buffer_advance_read_ptr(self.raw.buffer, bytes as c_int) };
+        count
+    }
+
+    /// Returns the number of elements the ring buffer can hold.
+    pub fn capacity(&self) -> usize {
+        capacity::<T>(&self.raw)
+    }
+}
+
+fn capacity<T>(raw: &RawRingBuffer) -> usize {
+    let bytes = unsafe { ffi::soundio_ring_buffer_capacity(raw.buffer) };
+    bytes as usize / mem::size_of::<T>()
+}
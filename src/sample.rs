@@ -0,0 +1,685 @@
+use std::{cmp, fmt, mem, ptr, slice};
+
+use ffi;
+use ffi::enums::SioFormat;
+
+/// A Rust type that can be written to and read from a stream of any `SioFormat`.
+///
+/// Samples are converted through a normalized `f64` in the range of `[-1.0, 1.0]`,
+/// values outside of this range are clamped.
+/// No conversion happens if `Sample::format` matches the format of the stream.
+///
+/// The trait is sealed, every bit pattern of a `Sample` has to be a valid value
+/// because device memory is accessed as `Sample`s.
+pub trait Sample: Copy + sealed::Sealed {
+    /// Returns the format that stores `Self` in native byte order.
+    fn format() -> SioFormat;
+
+    /// Converts the sample into the range of `[-1.0, 1.0]`.
+    fn to_f64(self) -> f64;
+
+    /// Converts `value` into a sample, `value` is clamped to `[-1.0, 1.0]`.
+    fn from_f64(value: f64) -> Self;
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Returns the format of `formats` with the highest `Codec::rank`.
+pub fn best_format(formats: &[SioFormat]) -> Option<SioFormat> {
+    formats.iter()
+           .filter_map(|&format| Codec::new(format).map(|codec| (codec.rank(), format)))
+           .max_by_key(|&(rank, _)| rank)
+           .map(|(_, format)| format)
+}
+
+/// Selects the little or big-endian `format` depending on the target.
+fn native(le: SioFormat, be: SioFormat) -> SioFormat {
+    if cfg!(target_endian = "little") {
+        le
+    } else {
+        be
+    }
+}
+
+fn clamp(value: f64) -> f64 {
+    if value.is_nan() {
+        0.0
+    } else {
+        value.clamp(-1.0, 1.0)
+    }
+}
+
+/// Scales `value` to a signed integer with `bits` bits, `1.0` maps to the largest value.
+fn to_int(value: f64, bits: u32) -> i64 {
+    let scale = (1i64 << (bits - 1)) as f64;
+    cmp::min((clamp(value) * scale).round() as i64, (1i64 << (bits - 1)) - 1)
+}
+
+fn from_int(value: i64, bits: u32) -> f64 {
+    value as f64 / (1i64 << (bits - 1)) as f64
+}
+
+macro_rules! signed_sample {
+    ($t:ty, $bits:expr, $le:expr, $be:expr) => (
+        impl sealed::Sealed for $t {}
+        impl Sample for $t {
+            fn format() -> SioFormat {
+                native($le, $be)
+            }
+
+            fn to_f64(self) -> f64 {
+                from_int(self as i64, $bits)
+            }
+
+            fn from_f64(value: f64) -> Self {
+                to_int(value, $bits) as $t
+            }
+        }
+    )
+}
+
+macro_rules! unsigned_sample {
+    ($t:ty, $bits:expr, $le:expr, $be:expr) => (
+        impl sealed::Sealed for $t {}
+        impl Sample for $t {
+            fn format() -> SioFormat {
+                native($le, $be)
+            }
+
+            fn to_f64(self) -> f64 {
+                from_int(self as i64 - (1i64 << ($bits - 1)), $bits)
+            }
+
+            fn from_f64(value: f64) -> Self {
+                (to_int(value, $bits) + (1i64 << ($bits - 1))) as $t
+            }
+        }
+    )
+}
+
+signed_sample!(i8, 8, SioFormat::S8, SioFormat::S8);
+unsigned_sample!(u8, 8, SioFormat::U8, SioFormat::U8);
+signed_sample!(i16, 16, SioFormat::S16LE, SioFormat::S16BE);
+unsigned_sample!(u16, 16, SioFormat::U16LE, SioFormat::U16BE);
+signed_sample!(i32, 32, SioFormat::S32LE, SioFormat::S32BE);
+unsigned_sample!(u32, 32, SioFormat::U32LE, SioFormat::U32BE);
+
+impl sealed::Sealed for f32 {}
+impl Sample for f32 {
+    fn format() -> SioFormat {
+        native(SioFormat::Float32LE, SioFormat::Float32BE)
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn from_f64(value: f64) -> Self {
+        clamp(value) as f32
+    }
+}
+
+impl sealed::Sealed for f64 {}
+impl Sample for f64 {
+    fn format() -> SioFormat {
+        native(SioFormat::Float64LE, SioFormat::Float64BE)
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn from_f64(value: f64) -> Self {
+        clamp(value)
+    }
+}
+
+/// A signed 24 bit sample, stored in the lower 3 bytes of an `i32`.
+///
+/// The most significant byte is ignored, so samples read from
+/// a 4 byte container don't need to be sign extended.
+#[derive(Clone, Copy, Default)]
+pub struct I24(i32);
+impl I24 {
+    /// Returns `None` if `value` does not fit into 24 bits.
+    pub fn new(value: i32) -> Option<I24> {
+        if value < I24::min_value().get() || value > I24::max_value().get() {
+            None
+        } else {
+            Some(I24(value))
+        }
+    }
+
+    pub fn min_value() -> I24 {
+        I24(-(1 << 23))
+    }
+
+    pub fn max_value() -> I24 {
+        I24((1 << 23) - 1)
+    }
+
+    /// Returns the sign extended value.
+    pub fn get(self) -> i32 {
+        (self.0 << 8) >> 8
+    }
+}
+impl PartialEq for I24 {
+    fn eq(&self, other: &I24) -> bool {
+        self.get() == other.get()
+    }
+}
+impl Eq for I24 {}
+impl fmt::Debug for I24 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "I24({})", self.get())
+    }
+}
+impl From<I24> for i32 {
+    fn from(sample: I24) -> i32 {
+        sample.get()
+    }
+}
+impl sealed::Sealed for I24 {}
+impl Sample for I24 {
+    fn format() -> SioFormat {
+        native(SioFormat::S24LE, SioFormat::S24BE)
+    }
+
+    fn to_f64(self) -> f64 {
+        from_int(self.get() as i64, 24)
+    }
+
+    fn from_f64(value: f64) -> Self {
+        I24(to_int(value, 24) as i32)
+    }
+}
+
+/// An unsigned 24 bit sample, stored in the lower 3 bytes of an `u32`.
+///
+/// The most significant byte is ignored.
+#[derive(Clone, Copy, Default)]
+pub struct U24(u32);
+impl U24 {
+    /// Returns `None` if `value` does not fit into 24 bits.
+    pub fn new(value: u32) -> Option<U24> {
+        if value > U24::max_value().get() {
+            None
+        } else {
+            Some(U24(value))
+        }
+    }
+
+    pub fn min_value() -> U24 {
+        U24(0)
+    }
+
+    pub fn max_value() -> U24 {
+        U24((1 << 24) - 1)
+    }
+
+    /// Returns the value without the most significant byte.
+    pub fn get(self) -> u32 {
+        self.0 & 0xff_ffff
+    }
+}
+impl PartialEq for U24 {
+    fn eq(&self, other: &U24) -> bool {
+        self.get() == other.get()
+    }
+}
+impl Eq for U24 {}
+impl fmt::Debug for U24 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "U24({})", self.get())
+    }
+}
+impl From<U24> for u32 {
+    fn from(sample: U24) -> u32 {
+        sample.get()
+    }
+}
+impl sealed::Sealed for U24 {}
+impl Sample for U24 {
+    fn format() -> SioFormat {
+        native(SioFormat::U24LE, SioFormat::U24BE)
+    }
+
+    fn to_f64(self) -> f64 {
+        from_int(self.get() as i64 - (1i64 << 23), 24)
+    }
+
+    fn from_f64(value: f64) -> Self {
+        U24((to_int(value, 24) + (1i64 << 23)) as u32)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Encoding {
+    Signed(u32),
+    Unsigned(u32),
+    Float,
+}
+
+/// Converts `Sample`s from and to the raw bytes of a stream format.
+#[derive(Clone, Copy, Debug)]
+pub struct Codec {
+    format: SioFormat,
+    encoding: Encoding,
+    big_endian: bool,
+    bytes: usize,
+}
+impl Codec {
+    /// Returns `None` for `SioFormat::Invalid`.
+    pub fn new(format: SioFormat) -> Option<Codec> {
+        Codec::with_bytes(format, unsafe { ffi::soundio_get_bytes_per_sample(format) } as usize)
+    }
+
+    /// Creates a codec for samples of `bytes` bytes, e.g. 3 for packed 24 bit samples.
+    fn with_bytes(format: SioFormat, bytes: usize) -> Option<Codec> {
+        let (encoding, big_endian) = match format {
+            SioFormat::Invalid => return None,
+            SioFormat::S8 => (Encoding::Signed(8), false),
+            SioFormat::U8 => (Encoding::Unsigned(8), false),
+            SioFormat::S16LE => (Encoding::Signed(16), false),
+            SioFormat::S16BE => (Encoding::Signed(16), true),
+            SioFormat::U16LE => (Encoding::Unsigned(16), false),
+            SioFormat::U16BE => (Encoding::Unsigned(16), true),
+            SioFormat::S24LE => (Encoding::Signed(24), false),
+            SioFormat::S24BE => (Encoding::Signed(24), true),
+            SioFormat::U24LE => (Encoding::Unsigned(24), false),
+            SioFormat::U24BE => (Encoding::Unsigned(24), true),
+            SioFormat::S32LE => (Encoding::Signed(32), false),
+            SioFormat::S32BE => (Encoding::Signed(32), true),
+            SioFormat::U32LE => (Encoding::Unsigned(32), false),
+            SioFormat::U32BE => (Encoding::Unsigned(32), true),
+            SioFormat::Float32LE | SioFormat::Float64LE => (Encoding::Float, false),
+            SioFormat::Float32BE | SioFormat::Float64BE => (Encoding::Float, true),
+        };
+        Some(Codec {
+            format: format,
+            encoding: encoding,
+            big_endian: big_endian,
+            bytes: bytes,
+        })
+    }
+
+    /// Ranks the format for `Device::negotiate_format`, higher is better.
+    /// Native-endian floats come first. The other formats are ranked by their
+    /// resolution and only then by their endianness, so a byte-swapped float
+    /// still beats a native-endian integer with fewer bits.
+    pub fn rank(&self) -> u32 {
+        let native_endian = self.bytes == 1 || self.big_endian == cfg!(target_endian = "big");
+        let (bits, kind) = match self.encoding {
+            Encoding::Float if native_endian => return 1000 - self.bytes as u32,
+            Encoding::Float => (self.bytes as u32 * 8, 2),
+            Encoding::Signed(bits) => (bits, 1),
+            Encoding::Unsigned(bits) => (bits, 0),
+        };
+        bits * 8 + native_endian as u32 * 4 + kind
+    }
+
+    /// `S` can be copied as is, e.g. `I24` to a 4 byte `S24LE` container
+    /// on a little-endian target, but not to packed 3 byte samples.
+    pub fn is_native<S: Sample>(&self) -> bool {
+        S::format() == self.format && mem::size_of::<S>() == self.bytes
+    }
+
+    /// Writes `sample` to `dst`, which must be valid for `bytes_per_sample` bytes.
+    pub unsafe fn write<S: Sample>(&self, sample: S, dst: *mut u8) {
+        if self.is_native::<S>() {
+            ptr::write_unaligned(dst as *mut S, sample)
+        } else {
+            self.encode(sample.to_f64(), slice::from_raw_parts_mut(dst, self.bytes))
+        }
+    }
+
+    /// Reads a sample from `src`, which must be valid for `bytes_per_sample` bytes.
+    pub unsafe fn read<S: Sample>(&self, src: *const u8) -> S {
+        if self.is_native::<S>() {
+            ptr::read_unaligned(src as *const S)
+        } else {
+            S::from_f64(self.decode(slice::from_raw_parts(src, self.bytes)))
+        }
+    }
+
+    fn encode(&self, value: f64, dst: &mut [u8]) {
+        let raw = match self.encoding {
+            Encoding::Signed(bits) => to_int(value, bits) as u64,
+            Encoding::Unsigned(bits) => (to_int(value, bits) + (1i64 << (bits - 1))) as u64,
+            Encoding::Float if self.bytes == 4 => (clamp(value) as f32).to_bits() as u64,
+            Encoding::Float => clamp(value).to_bits(),
+        };
+        for (idx, byte) in dst.iter_mut().enumerate() {
+            *byte = (raw >> self.shift(idx)) as u8;
+        }
+    }
+
+    fn decode(&self, src: &[u8]) -> f64 {
+        let raw = src.iter()
+                     .enumerate()
+                     .fold(0u64, |raw, (idx, &byte)| raw | (byte as u64) << self.shift(idx));
+        match self.encoding {
+            // sign extend from the most significant bit
+            Encoding::Signed(bits) => from_int((raw << (64 - bits)) as i64 >> (64 - bits), bits),
+            Encoding::Unsigned(bits) => {
+                let mask = (1u64 << bits) - 1;
+                from_int((raw & mask) as i64 - (1i64 << (bits - 1)), bits)
+            }
+            Encoding::Float if self.bytes == 4 => f32::from_bits(raw as u32) as f64,
+            Encoding::Float => f64::from_bits(raw),
+        }
+    }
+
+    /// Returns the shift of the byte at `idx` in the little-endian representation.
+    fn shift(&self, idx: usize) -> usize {
+        if self.big_endian {
+            (self.bytes - 1 - idx) * 8
+        } else {
+            idx * 8
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALUES: [f64; 4] = [-1.0, 0.0, 0.5, 1.0];
+
+    fn encoded(format: SioFormat, bytes: usize, value: f64) -> Vec<u8> {
+        let mut dst = vec![0xaa; bytes];
+        Codec::with_bytes(format, bytes).unwrap().encode(value, &mut dst);
+        dst
+    }
+
+    fn assert_encoded(format: SioFormat, bytes: usize, expected: [&[u8]; 4]) {
+        for (&value, &expected) in VALUES.iter().zip(expected.iter()) {
+            assert_eq!(encoded(format, bytes, value),
+                       expected,
+                       "{:?} in {} bytes, value {}",
+                       format,
+                       bytes,
+                       value);
+        }
+    }
+
+    #[test]
+    fn test_encode_8_and_16_bit() {
+        assert_encoded(SioFormat::S8, 1, [&[0x80], &[0x00], &[0x40], &[0x7f]]);
+        assert_encoded(SioFormat::U8, 1, [&[0x00], &[0x80], &[0xc0], &[0xff]]);
+        assert_encoded(SioFormat::S16LE,
+                       2,
+                       [&[0x00, 0x80], &[0x00, 0x00], &[0x00, 0x40], &[0xff, 0x7f]]);
+        assert_encoded(SioFormat::S16BE,
+                       2,
+                       [&[0x80, 0x00], &[0x00, 0x00], &[0x40, 0x00], &[0x7f, 0xff]]);
+        assert_encoded(SioFormat::U16LE,
+                       2,
+                       [&[0x00, 0x00], &[0x00, 0x80], &[0x00, 0xc0], &[0xff, 0xff]]);
+        assert_encoded(SioFormat::U16BE,
+                       2,
+                       [&[0x00, 0x00], &[0x80, 0x00], &[0xc0, 0x00], &[0xff, 0xff]]);
+    }
+
+    #[test]
+    fn test_encode_24_bit() {
+        // the padding byte of a 4 byte container holds the sign extension
+        assert_encoded(SioFormat::S24LE,
+                       4,
+                       [&[0x00, 0x00, 0x80, 0xff],
+                        &[0x00, 0x00, 0x00, 0x00],
+                        &[0x00, 0x00, 0x40, 0x00],
+                        &[0xff, 0xff, 0x7f, 0x00]]);
+        assert_encoded(SioFormat::S24BE,
+                       4,
+                       [&[0xff, 0x80, 0x00, 0x00],
+                        &[0x00, 0x00, 0x00, 0x00],
+                        &[0x00, 0x40, 0x00, 0x00],
+                        &[0x00, 0x7f, 0xff, 0xff]]);
+        assert_encoded(SioFormat::U24LE,
+                       4,
+                       [&[0x00, 0x00, 0x00, 0x00],
+                        &[0x00, 0x00, 0x80, 0x00],
+                        &[0x00, 0x00, 0xc0, 0x00],
+                        &[0xff, 0xff, 0xff, 0x00]]);
+        assert_encoded(SioFormat::U24BE,
+                       4,
+                       [&[0x00, 0x00, 0x00, 0x00],
+                        &[0x00, 0x80, 0x00, 0x00],
+                        &[0x00, 0xc0, 0x00, 0x00],
+                        &[0x00, 0xff, 0xff, 0xff]]);
+        assert_encoded(SioFormat::S24LE,
+                       3,
+                       [&[0x00, 0x00, 0x80],
+                        &[0x00, 0x00, 0x00],
+                        &[0x00, 0x00, 0x40],
+                        &[0xff, 0xff, 0x7f]]);
+        assert_encoded(SioFormat::S24BE,
+                       3,
+                       [&[0x80, 0x00, 0x00],
+                        &[0x00, 0x00, 0x00],
+                        &[0x40, 0x00, 0x00],
+                        &[0x7f, 0xff, 0xff]]);
+        assert_encoded(SioFormat::U24LE,
+                       3,
+                       [&[0x00, 0x00, 0x00],
+                        &[0x00, 0x00, 0x80],
+                        &[0x00, 0x00, 0xc0],
+                        &[0xff, 0xff, 0xff]]);
+        assert_encoded(SioFormat::U24BE,
+                       3,
+                       [&[0x00, 0x00, 0x00],
+                        &[0x80, 0x00, 0x00],
+                        &[0xc0, 0x00, 0x00],
+                        &[0xff, 0xff, 0xff]]);
+    }
+
+    #[test]
+    fn test_encode_32_bit() {
+        assert_encoded(SioFormat::S32LE,
+                       4,
+                       [&[0x00, 0x00, 0x00, 0x80],
+                        &[0x00, 0x00, 0x00, 0x00],
+                        &[0x00, 0x00, 0x00, 0x40],
+                        &[0xff, 0xff, 0xff, 0x7f]]);
+        assert_encoded(SioFormat::S32BE,
+                       4,
+                       [&[0x80, 0x00, 0x00, 0x00],
+                        &[0x00, 0x00, 0x00, 0x00],
+                        &[0x40, 0x00, 0x00, 0x00],
+                        &[0x7f, 0xff, 0xff, 0xff]]);
+        assert_encoded(SioFormat::U32LE,
+                       4,
+                       [&[0x00, 0x00, 0x00, 0x00],
+                        &[0x00, 0x00, 0x00, 0x80],
+                        &[0x00, 0x00, 0x00, 0xc0],
+                        &[0xff, 0xff, 0xff, 0xff]]);
+        assert_encoded(SioFormat::U32BE,
+                       4,
+                       [&[0x00, 0x00, 0x00, 0x00],
+                        &[0x80, 0x00, 0x00, 0x00],
+                        &[0xc0, 0x00, 0x00, 0x00],
+                        &[0xff, 0xff, 0xff, 0xff]]);
+    }
+
+    #[test]
+    fn test_encode_float() {
+        assert_encoded(SioFormat::Float32LE,
+                       4,
+                       [&[0x00, 0x00, 0x80, 0xbf],
+                        &[0x00, 0x00, 0x00, 0x00],
+                        &[0x00, 0x00, 0x00, 0x3f],
+                        &[0x00, 0x00, 0x80, 0x3f]]);
+        assert_encoded(SioFormat::Float32BE,
+                       4,
+                       [&[0xbf, 0x80, 0x00, 0x00],
+                        &[0x00, 0x00, 0x00, 0x00],
+                        &[0x3f, 0x00, 0x00, 0x00],
+                        &[0x3f, 0x80, 0x00, 0x00]]);
+        assert_encoded(SioFormat::Float64LE,
+                       8,
+                       [&[0, 0, 0, 0, 0, 0, 0xf0, 0xbf],
+                        &[0, 0, 0, 0, 0, 0, 0x00, 0x00],
+                        &[0, 0, 0, 0, 0, 0, 0xe0, 0x3f],
+                        &[0, 0, 0, 0, 0, 0, 0xf0, 0x3f]]);
+        assert_encoded(SioFormat::Float64BE,
+                       8,
+                       [&[0xbf, 0xf0, 0, 0, 0, 0, 0, 0],
+                        &[0x00, 0x00, 0, 0, 0, 0, 0, 0],
+                        &[0x3f, 0xe0, 0, 0, 0, 0, 0, 0],
+                        &[0x3f, 0xf0, 0, 0, 0, 0, 0, 0]]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let formats = [(SioFormat::S8, 1, 8),
+                       (SioFormat::U8, 1, 8),
+                       (SioFormat::S16LE, 2, 16),
+                       (SioFormat::S16BE, 2, 16),
+                       (SioFormat::U16LE, 2, 16),
+                       (SioFormat::U16BE, 2, 16),
+                       (SioFormat::S24LE, 3, 24),
+                       (SioFormat::S24LE, 4, 24),
+                       (SioFormat::S24BE, 3, 24),
+                       (SioFormat::S24BE, 4, 24),
+                       (SioFormat::U24LE, 3, 24),
+                       (SioFormat::U24LE, 4, 24),
+                       (SioFormat::U24BE, 3, 24),
+                       (SioFormat::U24BE, 4, 24),
+                       (SioFormat::S32LE, 4, 32),
+                       (SioFormat::S32BE, 4, 32),
+                       (SioFormat::U32LE, 4, 32),
+                       (SioFormat::U32BE, 4, 32),
+                       (SioFormat::Float32LE, 4, 0),
+                       (SioFormat::Float32BE, 4, 0),
+                       (SioFormat::Float64LE, 8, 0),
+                       (SioFormat::Float64BE, 8, 0)];
+        // floats are not clamped to an integer range and use 0 bits
+        for &(format, bytes, bits) in formats.iter() {
+            let codec = Codec::with_bytes(format, bytes).unwrap();
+            for &value in VALUES.iter() {
+                let decoded = codec.decode(&encoded(format, bytes, value));
+                // `1.0` is clamped to the largest integer, one step below
+                let expected = if value == 1.0 && bits > 0 {
+                    1.0 - from_int(1, bits)
+                } else {
+                    value
+                };
+                assert_eq!(decoded, expected, "{:?} in {} bytes", format, bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn test_24_bit_containers() {
+        // the same samples take 4 bytes in a container and 3 bytes packed
+        for &(format, le) in [(SioFormat::S24LE, true), (SioFormat::S24BE, false)].iter() {
+            let container = Codec::with_bytes(format, 4).unwrap();
+            let packed = Codec::with_bytes(format, 3).unwrap();
+            for &value in VALUES.iter() {
+                let wide = encoded(format, 4, value);
+                let narrow = encoded(format, 3, value);
+                let data = if le { &wide[..3] } else { &wide[1..] };
+                assert_eq!(data, &narrow[..]);
+                assert_eq!(container.decode(&wide), packed.decode(&narrow));
+            }
+            let sample = I24::new(-1234).unwrap();
+            let mut wide = [0u8; 4];
+            let mut narrow = [0u8; 3];
+            unsafe {
+                container.write(sample, wide.as_mut_ptr());
+                packed.write(sample, narrow.as_mut_ptr());
+                assert_eq!(container.read::<I24>(wide.as_ptr()), sample);
+                assert_eq!(packed.read::<I24>(narrow.as_ptr()), sample);
+            }
+        }
+        assert!(!Codec::with_bytes(I24::format(), 3).unwrap().is_native::<I24>());
+        assert!(Codec::with_bytes(I24::format(), 4).unwrap().is_native::<I24>());
+    }
+
+    fn read_i24(format: SioFormat, src: &[u8]) -> i32 {
+        let codec = Codec::with_bytes(format, src.len()).unwrap();
+        let sample = unsafe { codec.read::<I24>(src.as_ptr()) };
+        assert_eq!(codec.decode(src), sample.to_f64());
+        sample.get()
+    }
+
+    fn read_u24(format: SioFormat, src: &[u8]) -> u32 {
+        let codec = Codec::with_bytes(format, src.len()).unwrap();
+        let sample = unsafe { codec.read::<U24>(src.as_ptr()) };
+        assert_eq!(codec.decode(src), sample.to_f64());
+        sample.get()
+    }
+
+    #[test]
+    fn test_decode_signed_24_bit() {
+        let samples = [([0x56, 0x34, 0x12], 0x123456),
+                       ([0xaa, 0xcb, 0xed], -0x123456),
+                       ([0x00, 0x00, 0x80], -0x800000),
+                       ([0xff, 0xff, 0x7f], 0x7fffff),
+                       ([0xff, 0xff, 0xff], -1)];
+        for &(le, value) in samples.iter() {
+            let be = [le[2], le[1], le[0]];
+            assert_eq!(read_i24(SioFormat::S24LE, &le), value);
+            assert_eq!(read_i24(SioFormat::S24BE, &be), value);
+            // the padding byte of a 4 byte container is ignored
+            for &pad in [0x00, 0xff, 0x5a].iter() {
+                assert_eq!(read_i24(SioFormat::S24LE, &[le[0], le[1], le[2], pad]), value);
+                assert_eq!(read_i24(SioFormat::S24BE, &[pad, be[0], be[1], be[2]]), value);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_unsigned_24_bit() {
+        let samples = [([0x56, 0x34, 0x12], 0x123456),
+                       ([0x00, 0x00, 0x00], 0),
+                       ([0x00, 0x00, 0x80], 0x800000),
+                       ([0xff, 0xff, 0xff], 0xffffff)];
+        for &(le, value) in samples.iter() {
+            let be = [le[2], le[1], le[0]];
+            assert_eq!(read_u24(SioFormat::U24LE, &le), value);
+            assert_eq!(read_u24(SioFormat::U24BE, &be), value);
+            for &pad in [0x00, 0xff, 0x5a].iter() {
+                assert_eq!(read_u24(SioFormat::U24LE, &[le[0], le[1], le[2], pad]), value);
+                assert_eq!(read_u24(SioFormat::U24BE, &[pad, be[0], be[1], be[2]]), value);
+            }
+        }
+        // the middle of the unsigned range is silence
+        let codec = Codec::with_bytes(SioFormat::U24LE, 3).unwrap();
+        assert_eq!(codec.decode(&[0x00, 0x00, 0x80]), 0.0);
+        assert_eq!(codec.decode(&[0x00, 0x00, 0x00]), -1.0);
+    }
+
+    #[test]
+    fn test_clamp() {
+        assert_eq!(encoded(SioFormat::S16LE, 2, 2.0), encoded(SioFormat::S16LE, 2, 1.0));
+        assert_eq!(encoded(SioFormat::S16LE, 2, -2.0), encoded(SioFormat::S16LE, 2, -1.0));
+        assert_eq!(encoded(SioFormat::Float32LE, 4, f64::NAN),
+                   encoded(SioFormat::Float32LE, 4, 0.0));
+    }
+
+    #[test]
+    fn test_best_format() {
+        let f32_ne = native(SioFormat::Float32LE, SioFormat::Float32BE);
+        let f32_fe = native(SioFormat::Float32BE, SioFormat::Float32LE);
+        let f64_ne = native(SioFormat::Float64LE, SioFormat::Float64BE);
+        let f64_fe = native(SioFormat::Float64BE, SioFormat::Float64LE);
+        let s16_ne = native(SioFormat::S16LE, SioFormat::S16BE);
+        let s32_ne = native(SioFormat::S32LE, SioFormat::S32BE);
+        let s32_fe = native(SioFormat::S32BE, SioFormat::S32LE);
+        assert_eq!(best_format(&[]), None);
+        assert_eq!(best_format(&[SioFormat::Invalid]), None);
+        assert_eq!(best_format(&[s32_ne, f64_ne, f32_ne, f32_fe]), Some(f32_ne));
+        assert_eq!(best_format(&[s32_ne, f64_ne, f32_fe]), Some(f64_ne));
+        // a device that only exposes foreign-endian floats
+        assert_eq!(best_format(&[SioFormat::S8, SioFormat::U8, f32_fe]), Some(f32_fe));
+        assert_eq!(best_format(&[SioFormat::S8, f32_fe, f64_fe]), Some(f64_fe));
+        assert_eq!(best_format(&[s16_ne, f32_fe]), Some(f32_fe));
+        // the same resolution prefers native endianness, then floats
+        assert_eq!(best_format(&[f32_fe, s32_ne]), Some(s32_ne));
+        assert_eq!(best_format(&[s32_fe, f32_fe]), Some(f32_fe));
+    }
+}
@@ -0,0 +1,115 @@
+use ffi;
+use ffi::enums::SioChannelId;
+use base::{ChannelLayout, SioResult};
+use mixer::ChannelMixer;
+
+/// A coincident stereo pair, the first channel is the mid or left-facing microphone.
+#[derive(Clone, Copy)]
+enum Pair {
+    MidSide(usize, usize),
+    Xy(usize, usize),
+}
+impl Pair {
+    /// Finds `MsMid`/`MsSide` or `XyX`/`XyY` in `layout`, Mid/Side takes precedence.
+    fn find(layout: &ChannelLayout) -> SioResult<Pair> {
+        if let Some((mid, side)) = find_pair(layout, SioChannelId::MsMid, SioChannelId::MsSide) {
+            Ok(Pair::MidSide(mid, side))
+        } else if let Some((x, y)) = find_pair(layout, SioChannelId::XyX, SioChannelId::XyY) {
+            Ok(Pair::Xy(x, y))
+        } else {
+            Err(ffi::enums::SioError::Invalid)
+        }
+    }
+}
+
+fn find_pair(layout: &ChannelLayout,
+             first: SioChannelId,
+             second: SioChannelId)
+             -> Option<(usize, usize)> {
+    match (layout.find_channel(first), layout.find_channel(second)) {
+        (Some(first), Some(second)) => Some((first as usize, second as usize)),
+        _ => None,
+    }
+}
+
+/// Finds the left and right channel of `layout`, front channels take precedence over headphones.
+fn find_left_right(layout: &ChannelLayout) -> SioResult<(usize, usize)> {
+    find_pair(layout, SioChannelId::FrontLeft, SioChannelId::FrontRight)
+        .or_else(|| find_pair(layout, SioChannelId::HeadphonesLeft, SioChannelId::HeadphonesRight))
+        .ok_or(ffi::enums::SioError::Invalid)
+}
+
+impl ChannelMixer {
+    /// Creates a mixer that decodes the Mid/Side channels `SioChannelId::MsMid` and `MsSide`,
+    /// or the XY channels `XyX` and `XyY`, of `input` to the left and right channel of `output`.
+    /// It can be used with `OutStream::write_mixed` or on frames read from an input stream.
+    ///
+    /// `width` scales the side signal: `0.0` is mono, `1.0` keeps the recorded width
+    /// and larger values widen the image.
+    /// Decoding with a width of `1.0` reverts `ChannelMixer::stereo_encoder`.
+    ///
+    /// Returns `ffi::enums::SioError::Invalid` if `input` contains neither pair
+    /// or if `output` has no front left and right or headphone channels.
+    pub fn stereo_decoder(input: &ChannelLayout,
+                          output: &ChannelLayout,
+                          width: f32)
+                          -> SioResult<ChannelMixer> {
+        let pair = try!(Pair::find(input));
+        let (left, right) = try!(find_left_right(output));
+        let mut mixer = ChannelMixer::silent(input.channel_count() as usize,
+                                             output.channel_count() as usize);
+        match pair {
+            // L = M + S, R = M - S
+            Pair::MidSide(mid, side) => {
+                mixer.set_gain(mid, left, 1.0);
+                mixer.set_gain(mid, right, 1.0);
+                mixer.set_gain(side, left, width);
+                mixer.set_gain(side, right, -width);
+            }
+            Pair::Xy(x, y) => set_xy_gains(&mut mixer, (x, y), (left, right), width),
+        }
+        Ok(mixer)
+    }
+
+    /// Creates a mixer that encodes the left and right channel of `input` to
+    /// the Mid/Side channels `SioChannelId::MsMid` and `MsSide`,
+    /// or the XY channels `XyX` and `XyY`, of `output`.
+    ///
+    /// `width` scales the side signal like in `ChannelMixer::stereo_decoder`.
+    ///
+    /// Returns `ffi::enums::SioError::Invalid` if `output` contains neither pair
+    /// or if `input` has no front left and right or headphone channels.
+    pub fn stereo_encoder(input: &ChannelLayout,
+                          output: &ChannelLayout,
+                          width: f32)
+                          -> SioResult<ChannelMixer> {
+        let (left, right) = try!(find_left_right(input));
+        let pair = try!(Pair::find(output));
+        let mut mixer = ChannelMixer::silent(input.channel_count() as usize,
+                                             output.channel_count() as usize);
+        match pair {
+            // M = (L + R) / 2, S = (L - R) / 2
+            Pair::MidSide(mid, side) => {
+                mixer.set_gain(left, mid, 0.5);
+                mixer.set_gain(right, mid, 0.5);
+                mixer.set_gain(left, side, 0.5 * width);
+                mixer.set_gain(right, side, -0.5 * width);
+            }
+            Pair::Xy(x, y) => set_xy_gains(&mut mixer, (left, right), (x, y), width),
+        }
+        Ok(mixer)
+    }
+}
+
+/// XY and left/right only differ in width, so encoding and decoding use the same matrix.
+fn set_xy_gains(mixer: &mut ChannelMixer,
+                (in_left, in_right): (usize, usize),
+                (out_left, out_right): (usize, usize),
+                width: f32) {
+    let direct = 0.5 * (1.0 + width);
+    let cross = 0.5 * (1.0 - width);
+    mixer.set_gain(in_left, out_left, direct);
+    mixer.set_gain(in_right, out_left, cross);
+    mixer.set_gain(in_left, out_right, cross);
+    mixer.set_gain(in_right, out_right, direct);
+}
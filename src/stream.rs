@@ -1,26 +1,33 @@
 use std::os::raw::{c_int, c_double, c_void};
-use std::{ptr, slice};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{cmp, ptr, slice};
 use std::ffi::CString;
 
 use ffi;
 use base::*;
+use area::*;
+use sample::*;
+use mixer::{ChannelMixer, ChannelRouting};
 
 macro_rules! write_stream {
     ($name:ident, $t:ty) => (
         /// Expects a vector of `channel_count` channel `buffers`
         /// containing audio data that is written into
         /// the buffer of the output device.
-        /// NOTE: This is subject of change.
-        /// Passing an iterator gives more flexibility and should be more performant
-        /// in most cases.
+        /// `OutStream::write_interleaved` and `OutStream::write_from_iter`
+        /// don't need a buffer per channel.
+        ///
+        /// The samples are converted to the format of the stream, see `Sample`.
         ///
-        /// Returns the number of actually written frames.
+        /// Returns the number of actually written frames, which is at most
+        /// the length of the shortest buffer.
         /// If the provided buffers contain less frames
         /// than `min_frame_count`, or less buffers
         /// as `channel_count` are provided,
         /// then a `ffi::enums::SioError::Invalid` is returned.
         pub fn $name(&self, min_frame_count: u32, buffers: &[Vec<$t>]) -> SioResult<u32> {
-            let channel_count = self.layout().channel_count();
+            let channel_count = self.channel_count();
             // check if buffer contains frames for all channels
             if buffers.len() < channel_count as usize {
                 return Err(ffi::enums::SioError::Invalid);
@@ -30,16 +37,21 @@ macro_rules! write_stream {
                 return Err(ffi::enums::SioError::Invalid);
             }
 
-            // assuming that every channel buffer has the same length
-            let frame_count = buffers[0].len() as c_int;
+            let codec = try!(Codec::new(try!(self.format())).ok_or(ffi::enums::SioError::Invalid));
+            // the shortest channel buffer limits the frames, so writing never panics
+            // between `begin_write` and `end_write`
+            let frame_count = buffers[..channel_count as usize].iter()
+                                                               .map(|c| c.len())
+                                                               .min()
+                                                               .unwrap_or(0) as c_int;
             let mut raw_areas: *mut ffi::SoundIoChannelArea = ptr::null_mut();
-            let actual_frame_count = try!(self.begin_write(&mut raw_areas, &frame_count));
+            let actual_frame_count = try!(self.begin_write_raw(&mut raw_areas, &frame_count));
             let areas = unsafe { slice::from_raw_parts_mut(raw_areas, channel_count as usize) };
             for idx in 0..actual_frame_count as usize {
                 for channel in 0..channel_count as usize {
                     let area = areas[channel];
-                    let addr = (area.ptr as usize + area.step as usize * idx) as *mut $t;
-                    unsafe { *addr = buffers[channel][idx] };
+                    let addr = (area.ptr as usize + area.step as usize * idx) as *mut u8;
+                    unsafe { codec.write(buffers[channel][idx], addr) };
                 }
             }
             self.end_write().map_or(Ok(actual_frame_count), Err)
@@ -47,34 +59,140 @@ macro_rules! write_stream {
     )
 }
 
+/// Tells which callback of a stream panicked.
+///
+/// Panics are caught before they can unwind into libsoundio.
+/// Afterwards no callback of the stream is called anymore,
+/// an `OutStream` writes silence and an `InStream` drops all captured frames.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StreamFault {
+    WritePanicked = 1,
+    UnderflowPanicked = 2,
+    ReadPanicked = 3,
+    OverflowPanicked = 4,
+    ErrorPanicked = 5,
+}
+impl StreamFault {
+    fn load(fault: &AtomicUsize) -> Option<StreamFault> {
+        match fault.load(Ordering::SeqCst) {
+            1 => Some(StreamFault::WritePanicked),
+            2 => Some(StreamFault::UnderflowPanicked),
+            3 => Some(StreamFault::ReadPanicked),
+            4 => Some(StreamFault::OverflowPanicked),
+            5 => Some(StreamFault::ErrorPanicked),
+            _ => None,
+        }
+    }
+
+    // Only the first fault is kept.
+    fn store(self, fault: &AtomicUsize) {
+        let _ = fault.compare_exchange(0, self as usize, Ordering::SeqCst, Ordering::SeqCst);
+    }
+}
+
+/// Mixes the interleaved `frames` into frames of `MAX_CHANNELS` samples,
+/// so `OutStream::write_mixed` doesn't allocate.
+fn mixed_frames<'m>(mixer: &'m ChannelMixer,
+                    frames: &'m [f32])
+                    -> SioResult<impl Iterator<Item = [f32; MAX_CHANNELS as usize]> + 'm> {
+    let input_count = mixer.input_count();
+    if input_count == 0 || !frames.len().is_multiple_of(input_count) ||
+       mixer.output_count() > MAX_CHANNELS as usize {
+        return Err(ffi::enums::SioError::Invalid);
+    }
+    Ok(frames.chunks(input_count).map(move |frame| {
+        let mut out = [0.0f32; MAX_CHANNELS as usize];
+        mixer.mix(frame, &mut out);
+        out
+    }))
+}
+
+fn error_from_code(code: usize) -> ffi::enums::SioError {
+    use ffi::enums::SioError::*;
+    match code {
+        0 => None,
+        1 => NoMem,
+        2 => InitAudioBackend,
+        3 => SystemResources,
+        4 => OpeningDevice,
+        5 => NoSuchDevice,
+        7 => BackendUnavailable,
+        8 => Streaming,
+        9 => IncompatibleDevice,
+        10 => NoSuchClient,
+        11 => IncompatibleBackend,
+        12 => BackendDisconnected,
+        13 => Interrupted,
+        14 => Underflow,
+        15 => EncodingString,
+        _ => Invalid,
+    }
+}
+
+// The trampolines only borrow the fields of the callbacks they need,
+// because the stream might query its `fault` at the same time.
 extern "C" fn write_wrapper(raw_out: *mut ffi::SoundIoOutStream, min: c_int, max: c_int) {
-    let mut out = OutStream::new(raw_out);
-    out.marker = true;
-    let callbacks_ptr = unsafe { (*out.stream).userdata as *mut Box<OutStreamCallbacks> };
-    let callbacks: &mut Box<OutStreamCallbacks> = unsafe { &mut *callbacks_ptr };
-    callbacks.write.as_mut().map(|f| f(out, min as u32, max as u32));
+    let callbacks_ptr = unsafe { (*raw_out).userdata as *mut OutStreamCallbacks };
+    let fault = unsafe { &(*callbacks_ptr).fault };
+    let written = unsafe { &(*callbacks_ptr).written };
+    written.store(0, Ordering::SeqCst);
+    if StreamFault::load(fault).is_none() {
+        if let Some(ref mut f) = unsafe { &mut (*callbacks_ptr).write } {
+            let out = OutStream::from_callback(raw_out);
+            match panic::catch_unwind(AssertUnwindSafe(|| f(out, min as u32, max as u32))) {
+                Ok(()) => return,
+                Err(_) => StreamFault::WritePanicked.store(fault),
+            }
+        }
+    }
+    // a panicking callback may already have written some frames, at most `max` are allowed
+    let frames_left = (max as usize).saturating_sub(written.load(Ordering::SeqCst));
+    if let Some(err) = OutStream::from_callback(raw_out).write_silence(frames_left as u32) {
+        unsafe { &(*callbacks_ptr).silence_error }.store(err as usize, Ordering::SeqCst)
+    }
 }
 
 extern "C" fn underflow_wrapper(raw_out: *mut ffi::SoundIoOutStream) {
-    let mut out = OutStream::new(raw_out);
-    out.marker = true;
-    let callbacks_ptr = unsafe { (*out.stream).userdata as *mut Box<OutStreamCallbacks> };
-    let callbacks: &mut Box<OutStreamCallbacks> = unsafe { &mut *callbacks_ptr };
-    callbacks.underflow.as_mut().map(|f| f(out));
+    let callbacks_ptr = unsafe { (*raw_out).userdata as *mut OutStreamCallbacks };
+    let fault = unsafe { &(*callbacks_ptr).fault };
+    if StreamFault::load(fault).is_some() {
+        return;
+    }
+    if let Some(ref mut f) = unsafe { &mut (*callbacks_ptr).underflow } {
+        let out = OutStream::from_callback(raw_out);
+        if panic::catch_unwind(AssertUnwindSafe(|| f(out))).is_err() {
+            StreamFault::UnderflowPanicked.store(fault)
+        }
+    }
 }
 
 extern "C" fn error_wrapper(raw_out: *mut ffi::SoundIoOutStream, error: ffi::enums::SioError) {
-    let mut out = OutStream::new(raw_out);
-    out.marker = true;
-    let callbacks_ptr = unsafe { (*out.stream).userdata as *mut Box<OutStreamCallbacks> };
-    let callbacks: &mut Box<OutStreamCallbacks> = unsafe { &mut *callbacks_ptr };
-    callbacks.error.as_mut().map(|f| f(out, error));
+    let callbacks_ptr = unsafe { (*raw_out).userdata as *mut OutStreamCallbacks };
+    let fault = unsafe { &(*callbacks_ptr).fault };
+    if StreamFault::load(fault).is_some() {
+        return;
+    }
+    if let Some(ref mut f) = unsafe { &mut (*callbacks_ptr).error } {
+        let out = OutStream::from_callback(raw_out);
+        if panic::catch_unwind(AssertUnwindSafe(|| f(out, error))).is_err() {
+            StreamFault::ErrorPanicked.store(fault)
+        }
+    }
 }
 
+type WriteCallback<'a> = Box<FnMut(OutStream, u32, u32) + 'a>;
+type UnderflowCallback<'a> = Box<FnMut(OutStream) + 'a>;
+type OutErrorCallback<'a> = Box<FnMut(OutStream, ffi::enums::SioError) + 'a>;
+
 struct OutStreamCallbacks<'a> {
-    write: Option<Box<FnMut(OutStream, u32, u32) + 'a>>,
-    underflow: Option<Box<FnMut(OutStream) + 'a>>,
-    error: Option<Box<FnMut(OutStream, ffi::enums::SioError) + 'a>>,
+    write: Option<WriteCallback<'a>>,
+    underflow: Option<UnderflowCallback<'a>>,
+    error: Option<OutErrorCallback<'a>>,
+    fault: AtomicUsize,
+    // frames passed to `begin_write` during the current write callback
+    written: AtomicUsize,
+    // `SioError` of the last failed fallback to silence, `0` if there was none
+    silence_error: AtomicUsize,
 }
 impl<'a> Default for OutStreamCallbacks<'a> {
     fn default() -> Self {
@@ -82,25 +200,47 @@ impl<'a> Default for OutStreamCallbacks<'a> {
             write: None,
             underflow: None,
             error: None,
+            fault: AtomicUsize::new(0),
+            written: AtomicUsize::new(0),
+            silence_error: AtomicUsize::new(0),
         }
     }
 }
 
 /// An audio output stream, returned from a `Device`.
+///
+/// The stream can be moved freely, even after it was started,
+/// but it can't outlive the `SoundIo` context of its device.
+/// The `OutStream` passed to a callback does not own the stream, registering
+/// callbacks on it has no effect.
 pub struct OutStream<'a> {
     stream: *mut ffi::SoundIoOutStream,
-    callbacks: Box<OutStreamCallbacks<'a>>,
-    name: CString,
-    marker: bool,
+    // `None` if the stream is owned by someone else, i.e. inside of a callback.
+    callbacks: Option<Box<OutStreamCallbacks<'a>>>,
+    name: Option<CString>,
 }
 impl<'a> OutStream<'a> {
-    pub fn new(raw_stream: *mut ffi::SoundIoOutStream) -> Self {
-        let callbacks = Box::new(OutStreamCallbacks::default());
+    pub(crate) fn new(raw_stream: *mut ffi::SoundIoOutStream) -> Self {
+        let mut callbacks = Box::new(OutStreamCallbacks::default());
+        unsafe {
+            // The callbacks live on the heap, so `userdata` stays valid
+            // even if the stream is moved.
+            (*raw_stream).userdata = &mut *callbacks as *mut OutStreamCallbacks as *mut c_void;
+        }
         OutStream {
             stream: raw_stream,
-            callbacks: callbacks,
-            name: CString::new("outstream").unwrap(),
-            marker: false,
+            callbacks: Some(callbacks),
+            name: None,
+        }
+    }
+
+    /// Wraps the stream passed to a callback without taking ownership,
+    /// so it is neither destroyed on drop nor does it allocate.
+    fn from_callback(raw_stream: *mut ffi::SoundIoOutStream) -> Self {
+        OutStream {
+            stream: raw_stream,
+            callbacks: None,
+            name: None,
         }
     }
 
@@ -155,7 +295,8 @@ impl<'a> OutStream<'a> {
     /// Registers the given callback as `write_callback` that is called as soon as you call `start`.
     ///
     /// In this callback, you call `OutStream::write_stream_FMT` where `FMT` is one of the supported
-    /// format, `u16`, `f32` etc.
+    /// format, `u16`, `f32` etc., `OutStream::write_interleaved`, `OutStream::write_from_iter`
+    /// or `OutStream::begin_write`.
     ///
     /// `frame_count_max` will always be greater than 0. Note that you
     /// should write as many frames as you can; `frame_count_min` might be 0 and
@@ -170,17 +311,16 @@ impl<'a> OutStream<'a> {
     /// for a long time. This includes all I/O functions (disk, TTY, network),
     /// malloc, free, printf, pthread_mutex_lock, sleep, wait, poll, select,
     /// pthread_join, pthread_cond_wait, etc.
+    ///
+    /// If the callback panics, the stream writes silence from then on,
+    /// see `OutStream::fault`.
     pub fn register_write_callback<W>(&mut self, callback: W)
         where W: FnMut(OutStream, u32, u32) + 'a
     {
-        // stored box reference to callback closure
-        self.callbacks.write = Some(Box::new(callback));
-        unsafe {
+        if let Some(ref mut callbacks) = self.callbacks {
+            callbacks.write = Some(Box::new(callback));
             // register wrapper for write_callback
-            (*self.stream).write_callback = Some(write_wrapper);
-            // store reference to callbacks struct in userdata pointer
-            (*self.stream).userdata =
-                &self.callbacks as *const Box<OutStreamCallbacks> as *mut c_void
+            unsafe { (*self.stream).write_callback = Some(write_wrapper) }
         }
     }
 
@@ -191,13 +331,10 @@ impl<'a> OutStream<'a> {
     pub fn register_underflow_callback<U>(&mut self, callback: U)
         where U: FnMut(OutStream) + 'a
     {
-        self.callbacks.underflow = Some(Box::new(callback));
-        unsafe {
-            // register wrapper for write_callback
-            (*self.stream).underflow_callback = Some(underflow_wrapper);
-            // store reference to callbacks struct in userdata pointer
-            (*self.stream).userdata =
-                &self.callbacks as *const Box<OutStreamCallbacks> as *mut c_void
+        if let Some(ref mut callbacks) = self.callbacks {
+            callbacks.underflow = Some(Box::new(callback));
+            // register wrapper for underflow_callback
+            unsafe { (*self.stream).underflow_callback = Some(underflow_wrapper) }
         }
     }
 
@@ -210,13 +347,10 @@ impl<'a> OutStream<'a> {
     pub fn register_error_callback<E>(&mut self, callback: E)
         where E: FnMut(OutStream, ffi::enums::SioError) + 'a
     {
-        self.callbacks.error = Some(Box::new(callback));
-        unsafe {
-            // register wrapper for write_callback
-            (*self.stream).error_callback = Some(error_wrapper);
-            // store reference to callbacks struct in userdata pointer
-            (*self.stream).userdata =
-                &self.callbacks as *const Box<OutStreamCallbacks> as *mut c_void
+        if let Some(ref mut callbacks) = self.callbacks {
+            callbacks.error = Some(Box::new(callback));
+            // register wrapper for error_callback
+            unsafe { (*self.stream).error_callback = Some(error_wrapper) }
         }
     }
 
@@ -224,22 +358,181 @@ impl<'a> OutStream<'a> {
     write_stream!(write_stream_u8, u8);
     write_stream!(write_stream_i16, i16);
     write_stream!(write_stream_u16, u16);
+    write_stream!(write_stream_i24, I24);
+    write_stream!(write_stream_u24, U24);
     write_stream!(write_stream_i32, i32);
     write_stream!(write_stream_u32, u32);
     write_stream!(write_stream_f32, f32);
     write_stream!(write_stream_f64, f64);
 
-    fn begin_write(&self,
-                   areas: *mut *mut ffi::SoundIoChannelArea,
-                   frame_count: &c_int)
-                   -> SioResult<u32> {
+    /// Gives direct access to up to `frame_count` frames of the device buffer,
+    /// the samples are written in place through the returned `WriteGuard`.
+    /// The write is committed when the guard is dropped or `WriteGuard::end` is called.
+    ///
+    /// This function must be called only from within `OutStream::write_callback`.
+    /// The number of frames actually available is returned by
+    /// `WriteGuard::frame_count` and might be less than requested.
+    ///
+    /// Possible errors:
+    ///
+    /// - `ffi::enums::SioError::Invalid`
+    ///     - `frame_count` is 0
+    ///     - function called outside of `write_callback`
+    ///     - `frame_count` is greater than `frame_count_max`
+    /// - `ffi::enums::SioError::Streaming`
+    /// - `ffi::enums::SioError::IncompatibleDevice` - the channel layout changed
+    pub fn begin_write(&mut self, frame_count: u32) -> SioResult<WriteGuard<'_>> {
+        if frame_count == 0 || frame_count > c_int::MAX as u32 {
+            return Err(ffi::enums::SioError::Invalid);
+        }
+        let codec = try!(Codec::new(try!(self.format())).ok_or(ffi::enums::SioError::Invalid));
+        let channel_count = self.channel_count() as usize;
+        let mut raw_areas: *mut ffi::SoundIoChannelArea = ptr::null_mut();
+        let actual_frame_count = try!(self.begin_write_raw(&mut raw_areas, &(frame_count as c_int)));
+        let areas: &[ffi::SoundIoChannelArea] = if raw_areas.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(raw_areas, channel_count) }
+        };
+        Ok(WriteGuard {
+            stream: self.stream,
+            areas: areas,
+            frame_count: actual_frame_count as usize,
+            codec: codec,
+            ended: false,
+        })
+    }
+
+    /// Writes the interleaved samples of `frames`, i.e. `[l0, r0, l1, r1, ...]` for a stereo
+    /// stream, and returns the number of written frames.
+    /// The samples are converted to the format of the stream, see `Sample`,
+    /// and written in as many chunks as the device requires.
+    ///
+    /// This function must be called only from within `OutStream::write_callback`
+    /// and `frames` must not contain more than `frame_count_max` frames.
+    ///
+    /// Possible errors:
+    ///
+    /// - `ffi::enums::SioError::Invalid`
+    ///     - the length of `frames` is not a multiple of `channel_count`
+    /// - see `OutStream::begin_write` for the remaining errors
+    pub fn write_interleaved<S: Sample>(&mut self, frames: &[S]) -> SioResult<usize> {
+        let channel_count = self.channel_count() as usize;
+        if channel_count == 0 || !frames.len().is_multiple_of(channel_count) {
+            return Err(ffi::enums::SioError::Invalid);
+        }
+        let frame_count = frames.len() / channel_count;
+        self.write_from_iter(frame_count as u32, frames.chunks(channel_count))
+    }
+
+    /// Writes up to `frame_count` frames taken from `frames` and returns the number of
+    /// consumed frames. Each frame contains one sample per channel, additional samples are
+    /// ignored. The samples are converted to the format of the stream, see `Sample`,
+    /// and written in as many chunks as the device requires.
+    ///
+    /// If `frames` ends early, the rest of the current chunk is filled with silence.
+    ///
+    /// This function must be called only from within `OutStream::write_callback`
+    /// and `frame_count` must not be greater than `frame_count_max`.
+    ///
+    /// Possible errors:
+    ///
+    /// - `ffi::enums::SioError::Invalid` - a frame contains fewer samples than `channel_count`
+    /// - see `OutStream::begin_write` for the remaining errors
+    pub fn write_from_iter<S, F, I>(&mut self, frame_count: u32, frames: I) -> SioResult<usize>
+        where S: Sample,
+              F: AsRef<[S]>,
+              I: IntoIterator<Item = F>
+    {
+        let mut frames = frames.into_iter();
+        let mut frames_left = match frames.size_hint() {
+            (_, Some(upper)) => cmp::min(upper, frame_count as usize),
+            (_, None) => frame_count as usize,
+        };
+        let mut written = 0;
+        while frames_left > 0 {
+            let mut guard = try!(self.begin_write(frames_left as u32));
+            let chunk_size = guard.frame_count();
+            if chunk_size == 0 {
+                break;
+            }
+            for idx in 0..chunk_size {
+                let frame = match frames.next() {
+                    Some(frame) => frame,
+                    None => {
+                        // the whole chunk is played, so it must not contain stale samples
+                        guard.write_silence_from(idx);
+                        try!(guard.end());
+                        return Ok(written + idx);
+                    }
+                };
+                if let Err(err) = guard.write_frame(idx, frame.as_ref()) {
+                    guard.write_silence_from(idx);
+                    return Err(err);
+                }
+            }
+            try!(guard.end());
+            written += chunk_size;
+            frames_left -= chunk_size;
+        }
+        Ok(written)
+    }
+
+    /// Mixes the interleaved `frames` with `mixer` into the channels of the stream,
+    /// see `OutStream::write_interleaved`. Returns the number of written frames.
+    ///
+    /// Possible errors:
+    ///
+    /// - `ffi::enums::SioError::Invalid`
+    ///     - the length of `frames` is not a multiple of `ChannelMixer::input_count`
+    ///     - `ChannelMixer::output_count` differs from `channel_count`
+    ///       or is greater than 24, the channel limit of libsoundio
+    /// - see `OutStream::begin_write` for the remaining errors
+    pub fn write_mixed(&mut self, mixer: &ChannelMixer, frames: &[f32]) -> SioResult<usize> {
+        if mixer.output_count() != self.channel_count() as usize {
+            return Err(ffi::enums::SioError::Invalid);
+        }
+        let mixed = try!(mixed_frames(mixer, frames));
+        let frame_count = frames.len() / mixer.input_count();
+        self.write_from_iter(frame_count as u32, mixed)
+    }
+
+    /// Returns a `ChannelRouting` without routes from `source_count` channels
+    /// to the channel layout of the stream.
+    pub fn routing(&self, source_count: usize) -> ChannelRouting {
+        ChannelRouting::new(source_count, &self.layout())
+    }
+
+    /// Writes the interleaved `frames` with `source_count` channels, see `ChannelRouting::new`,
+    /// to the channels they are routed to. Returns the number of written frames.
+    ///
+    /// Possible errors:
+    ///
+    /// - `ffi::enums::SioError::Invalid`
+    ///     - the length of `frames` is not a multiple of `source_count`
+    ///     - the routing was made for a layout with a different channel count
+    /// - see `OutStream::begin_write` for the remaining errors
+    pub fn write_routed(&mut self, routing: &ChannelRouting, frames: &[f32]) -> SioResult<usize> {
+        self.write_mixed(routing.mixer(), frames)
+    }
+
+    fn begin_write_raw(&self,
+                       areas: *mut *mut ffi::SoundIoChannelArea,
+                       frame_count: &c_int)
+                       -> SioResult<u32> {
         let mut actual_frame_count = *frame_count as c_int;
         match unsafe {
             ffi::soundio_outstream_begin_write(self.stream,
                                                areas,
                                                &mut actual_frame_count as *mut c_int)
         } {
-            ffi::enums::SioError::None => Ok(actual_frame_count as u32),
+            ffi::enums::SioError::None => {
+                // the frames are committed by `end_write`, even if the callback panics
+                let callbacks_ptr = unsafe { (*self.stream).userdata as *const OutStreamCallbacks };
+                unsafe { &(*callbacks_ptr).written }
+                    .fetch_add(actual_frame_count as usize, Ordering::SeqCst);
+                Ok(actual_frame_count as u32)
+            }
             err => Err(err),
         }
     }
@@ -251,6 +544,53 @@ impl<'a> OutStream<'a> {
         }
     }
 
+    /// Writes up to `frame_count` frames of silence and returns the first error.
+    fn write_silence(&self, frame_count: u32) -> Option<ffi::enums::SioError> {
+        let codec = match self.format().ok().and_then(Codec::new) {
+            Some(codec) => codec,
+            None => return Some(ffi::enums::SioError::Invalid),
+        };
+        let channel_count = self.channel_count() as usize;
+        let mut frames_left = frame_count as c_int;
+        while frames_left > 0 {
+            let mut raw_areas: *mut ffi::SoundIoChannelArea = ptr::null_mut();
+            let actual_frame_count = match self.begin_write_raw(&mut raw_areas, &frames_left) {
+                Ok(0) => return None,
+                Ok(cnt) => cnt as usize,
+                Err(err) => return Some(err),
+            };
+            let areas = unsafe { slice::from_raw_parts(raw_areas, channel_count) };
+            for area in areas {
+                for idx in 0..actual_frame_count {
+                    let addr = (area.ptr as usize + area.step as usize * idx) as *mut u8;
+                    unsafe { codec.write(0.0f64, addr) };
+                }
+            }
+            if let Some(err) = self.end_write() {
+                return Some(err);
+            }
+            frames_left -= actual_frame_count as c_int;
+        }
+        None
+    }
+
+    /// Returns the `StreamFault` if one of the registered callbacks panicked.
+    /// In this case the stream keeps running but only writes silence.
+    pub fn fault(&self) -> Option<StreamFault> {
+        let callbacks_ptr = unsafe { (*self.stream).userdata as *const OutStreamCallbacks };
+        StreamFault::load(unsafe { &(*callbacks_ptr).fault })
+    }
+
+    /// Returns the error of the last failed attempt to write silence,
+    /// either after a `StreamFault` or if no `write_callback` is registered.
+    pub fn silence_error(&self) -> Option<ffi::enums::SioError> {
+        let callbacks_ptr = unsafe { (*self.stream).userdata as *const OutStreamCallbacks };
+        match unsafe { &(*callbacks_ptr).silence_error }.load(Ordering::SeqCst) {
+            0 => None,
+            code => Some(error_from_code(code)),
+        }
+    }
+
     /// Clears the output stream buffer.
     /// This function can be called from any thread.
     /// This function can be called regardless of whether the outstream is paused
@@ -397,11 +737,64 @@ impl<'a> OutStream<'a> {
         }
     }
 
+    /// Sets the stream format to the first format of `preferred` that the device supports,
+    /// or to the best supported format, see `Device::negotiate_format`, and returns it.
+    /// **Must** be called before `open`ing the stream.
+    ///
+    /// Samples are converted to the negotiated format when they are written,
+    /// so the application can stick to its own sample type, see `Sample`.
+    /// Pass `&[S::format()]` to avoid the conversion if possible.
+    ///
+    /// If the supported formats of the device are unknown
+    /// `ffi::enums::SioError::IncompatibleDevice` is returned.
+    pub fn negotiate_format(&self,
+                            preferred: &[ffi::enums::SioFormat])
+                            -> SioResult<ffi::enums::SioFormat> {
+        let format = try!(self.device()
+                              .negotiate_format(preferred)
+                              .ok_or(ffi::enums::SioError::IncompatibleDevice));
+        unsafe { (*self.stream).format = format };
+        Ok(format)
+    }
+
     /// Returns the channel layout of the output stream.
     pub fn layout(&self) -> ChannelLayout {
         ChannelLayout::new(unsafe { &(*self.stream).layout })
     }
 
+    // Doesn't copy the layout, so it can be used within the callbacks.
+    fn channel_count(&self) -> u32 {
+        unsafe { (*self.stream).layout.channel_count as u32 }
+    }
+
+    /// Sets the channel layout of the stream to `layout`.
+    /// **Must** be called before `open`ing the stream,
+    /// check `layout_error` afterwards.
+    ///
+    /// If the device doesn't support the layout
+    /// `ffi::enums::SioError::IncompatibleDevice` is returned.
+    pub fn set_layout(&self, layout: &ChannelLayout) -> SioResult<()> {
+        if self.device().supports_layout(layout) {
+            unsafe { (*self.stream).layout = layout.to_raw() };
+            Ok(())
+        } else {
+            Err(ffi::enums::SioError::IncompatibleDevice)
+        }
+    }
+
+    /// Sets the channel layout to the default layout for `channel_count` channels,
+    /// see `ChannelLayout::default` and `set_layout`.
+    ///
+    /// Possible errors:
+    ///
+    /// - `ffi::enums::SioError::Invalid` - there is no default layout for `channel_count`
+    /// - `ffi::enums::SioError::IncompatibleDevice` - the device doesn't support the layout
+    pub fn set_channel_count(&self, channel_count: u32) -> SioResult<()> {
+        let layout = try!(ChannelLayout::default(channel_count)
+                              .ok_or(ffi::enums::SioError::Invalid));
+        self.set_layout(&layout)
+    }
+
     /// Returns the sample rate of the output stream.
     pub fn sample_rate(&self) -> u32 {
         unsafe { (*self.stream).sample_rate as u32 }
@@ -411,13 +804,13 @@ impl<'a> OutStream<'a> {
     /// Make sure that the device supports the given sample rate to avoid
     /// sample rate conversions. A `Device` provides `supports_sample_rate` and
     /// `nearest_sample_rate` methods for this purpose.
-    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+    pub fn set_sample_rate(&self, sample_rate: u32) {
         unsafe { (*self.stream).sample_rate = sample_rate as c_int }
     }
 
     /// Returns the underlying device of the output stream.
-    pub fn device(&self) -> Device {
-        Device::new(unsafe { (*self.stream).device })
+    pub fn device(&self) -> Device<'a> {
+        Device::with_ref(unsafe { (*self.stream).device })
     }
 
     /// Sets the stream name to `name`.
@@ -427,10 +820,15 @@ impl<'a> OutStream<'a> {
     /// WASAPI uses this for the session display name.
     /// Colons (`:`) contained in `name` will be replaced with `_`.
     /// If the `name` contains a `NULL` byte, `SioError::EncodingString` is returned.
+    /// Returns `SioError::Invalid` if called on a stream passed to a callback.
     pub fn set_name<T: Into<String>>(&mut self, name: T) -> SioResult<()> {
+        if self.callbacks.is_none() {
+            return Err(ffi::enums::SioError::Invalid);
+        }
         let s = name.into().replace(":", "_");
-        self.name = try!(CString::new(s).map_err(|_| ffi::enums::SioError::EncodingString));
-        unsafe { (*self.stream).name = self.name.as_ptr() };
+        let name = try!(CString::new(s).map_err(|_| ffi::enums::SioError::EncodingString));
+        unsafe { (*self.stream).name = name.as_ptr() };
+        self.name = Some(name);
         Ok(())
     }
 
@@ -438,10 +836,7 @@ impl<'a> OutStream<'a> {
     pub fn name(&self) -> Option<String> {
         let s_ptr = unsafe { (*self.stream).name };
         if !s_ptr.is_null() {
-            match ffi::utils::ptr_to_string(s_ptr) {
-                Ok(s) => Some(s),
-                Err(_) => None,
-            }
+            ffi::utils::ptr_to_string(s_ptr).ok()
         } else {
             None
         }
@@ -452,11 +847,8 @@ impl<'a> OutStream<'a> {
     /// If the layout is compatible `()` is returned.
     pub fn layout_error(&self) -> SioResult<()> {
         match unsafe { (*self.stream).layout_error } {
-            0 => Ok(()),
-            e => {
-                println!("layout error: {}", e);
-                Ok(())
-            }
+            ffi::enums::SioError::None => Ok(()),
+            err => Err(err),
         }
     }
 
@@ -468,14 +860,685 @@ impl<'a> OutStream<'a> {
 }
 impl<'a> Drop for OutStream<'a> {
     fn drop(&mut self) {
-        // Only drop if usage `marker` is false.
-        // The usage marker is set by the callback function to prevent the
-        // source stream from dropping on the context switch of the callback function.
-        if !self.marker {
+        // Streams passed to a callback are owned by someone else.
+        if self.callbacks.is_some() {
             self.destroy()
+        }
+    }
+}
+
+/// Direct access to the device buffer of an `OutStream`, returned from `OutStream::begin_write`.
+///
+/// The samples are written in place, without an intermediate buffer.
+/// The write is committed when the guard is dropped, use `end` to get the result.
+pub struct WriteGuard<'s> {
+    stream: *mut ffi::SoundIoOutStream,
+    areas: &'s [ffi::SoundIoChannelArea],
+    frame_count: usize,
+    codec: Codec,
+    ended: bool,
+}
+impl<'s> WriteGuard<'s> {
+    /// Returns the number of frames that can be written.
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    /// Returns the number of channels of the stream.
+    pub fn channel_count(&self) -> usize {
+        self.areas.len()
+    }
+
+    /// Returns the samples of the channel at `idx`, typed as `T`.
+    /// The samples are not converted, `T::format` has to match the format of the stream.
+    ///
+    /// Possible errors:
+    ///
+    /// - `ffi::enums::SioError::Invalid`
+    ///     - `idx` is not smaller than `channel_count`
+    ///     - `T` does not match the format of the stream
+    /// - `ffi::enums::SioError::IncompatibleDevice` - the samples are not aligned for `T`
+    pub fn channel<T: Sample>(&mut self, idx: usize) -> SioResult<ChannelArea<'_, T>> {
+        try!(self.check_sample_type::<T>());
+        let area = try!(self.areas.get(idx).ok_or(ffi::enums::SioError::Invalid));
+        ChannelArea::new(area, self.frame_count).ok_or(ffi::enums::SioError::IncompatibleDevice)
+    }
+
+    /// Returns the samples of all channels, typed as `T`.
+    /// The samples are not converted, `T::format` has to match the format of the stream.
+    ///
+    /// Possible errors:
+    ///
+    /// - `ffi::enums::SioError::Invalid` - `T` does not match the format of the stream
+    /// - `ffi::enums::SioError::IncompatibleDevice` - the samples are not aligned for `T`
+    pub fn channels<T: Sample>(&mut self) -> SioResult<ChannelAreas<'_, T>> {
+        try!(self.check_sample_type::<T>());
+        ChannelAreas::new(self.areas, self.frame_count)
+            .ok_or(ffi::enums::SioError::IncompatibleDevice)
+    }
+
+    /// Commits the written frames.
+    ///
+    /// Possible errors:
+    ///
+    /// - `ffi::enums::SioError::Streaming`
+    /// - `ffi::enums::SioError::Underflow` - an underflow caused this call to fail.
+    ///   You might also get an `OutStream::underflow_callback`, and you might not get this error
+    ///   code when an underflow occurs. Unlike `ffi::enums::SioError::Streaming`,
+    ///   the outstream is still in a valid state and streaming can continue.
+    pub fn end(mut self) -> SioResult<()> {
+        self.ended = true;
+        match unsafe { ffi::soundio_outstream_end_write(self.stream) } {
+            ffi::enums::SioError::None => Ok(()),
+            err => Err(err),
+        }
+    }
+
+    fn check_sample_type<T: Sample>(&self) -> SioResult<()> {
+        if !self.codec.is_native::<T>() {
+            return Err(ffi::enums::SioError::Invalid);
+        }
+        Ok(())
+    }
+
+    /// Writes the samples of `frame` at frame index `idx`, one for each channel.
+    fn write_frame<S: Sample>(&mut self, idx: usize, frame: &[S]) -> SioResult<()> {
+        if frame.len() < self.areas.len() {
+            return Err(ffi::enums::SioError::Invalid);
+        }
+        for (area, &sample) in self.areas.iter().zip(frame.iter()) {
+            let addr = (area.ptr as usize + area.step as usize * idx) as *mut u8;
+            unsafe { self.codec.write(sample, addr) };
+        }
+        Ok(())
+    }
+
+    /// Writes silence from frame index `idx` up to `frame_count`.
+    fn write_silence_from(&mut self, idx: usize) {
+        for area in self.areas {
+            for frame in idx..self.frame_count {
+                let addr = (area.ptr as usize + area.step as usize * frame) as *mut u8;
+                unsafe { self.codec.write(0.0f64, addr) };
+            }
+        }
+    }
+}
+impl<'s> Drop for WriteGuard<'s> {
+    fn drop(&mut self) {
+        if !self.ended {
+            unsafe { ffi::soundio_outstream_end_write(self.stream) };
+        }
+    }
+}
+
+macro_rules! read_stream {
+    ($name:ident, $t:ty) => (
+        /// Reads at most `max_frame_count` frames from the buffer of the
+        /// input device and returns them as a vector of `channel_count`
+        /// channel buffers.
+        /// NOTE: This is subject of change.
+        ///
+        /// The samples are converted from the format of the stream, see `Sample`.
+        /// If the device buffer contains a hole, the returned buffers
+        /// are filled with silence for the missing frames.
+        /// If there are no frames to read, the returned channel buffers are empty.
+        pub fn $name(&self, max_frame_count: u32) -> SioResult<Vec<Vec<$t>>> {
+            let codec = try!(Codec::new(try!(self.format())).ok_or(ffi::enums::SioError::Invalid));
+            let channel_count = self.channel_count() as usize;
+            let frame_count = max_frame_count as c_int;
+            let mut raw_areas: *mut ffi::SoundIoChannelArea = ptr::null_mut();
+            let actual_frame_count = try!(self.begin_read(&mut raw_areas, &frame_count)) as usize;
+            let mut buffers: Vec<Vec<$t>> = (0..channel_count)
+                                                .map(|_| Vec::with_capacity(actual_frame_count))
+                                                .collect();
+            // nothing to read, `end_read` must not be called in this case
+            if actual_frame_count == 0 {
+                return Ok(buffers);
+            }
+            if raw_areas.is_null() {
+                // there is a hole in the buffer
+                for buffer in &mut buffers {
+                    buffer.resize(actual_frame_count, <$t>::from_f64(0.0));
+                }
+            } else {
+                let areas = unsafe { slice::from_raw_parts(raw_areas, channel_count) };
+                for idx in 0..actual_frame_count {
+                    for channel in 0..channel_count {
+                        let area = areas[channel];
+                        let addr = (area.ptr as usize + area.step as usize * idx) as *const u8;
+                        buffers[channel].push(unsafe { codec.read(addr) });
+                    }
+                }
+            }
+            self.end_read().map_or(Ok(buffers), Err)
+        }
+    )
+}
+
+extern "C" fn read_wrapper(raw_in: *mut ffi::SoundIoInStream, min: c_int, max: c_int) {
+    let callbacks_ptr = unsafe { (*raw_in).userdata as *mut InStreamCallbacks };
+    let fault = unsafe { &(*callbacks_ptr).fault };
+    if StreamFault::load(fault).is_none() {
+        if let Some(ref mut f) = unsafe { &mut (*callbacks_ptr).read } {
+            let input = InStream::from_callback(raw_in);
+            match panic::catch_unwind(AssertUnwindSafe(|| f(input, min as u32, max as u32))) {
+                Ok(()) => return,
+                Err(_) => StreamFault::ReadPanicked.store(fault),
+            }
+        }
+    }
+    InStream::from_callback(raw_in).drop_frames(max as u32);
+}
+
+extern "C" fn overflow_wrapper(raw_in: *mut ffi::SoundIoInStream) {
+    let callbacks_ptr = unsafe { (*raw_in).userdata as *mut InStreamCallbacks };
+    let fault = unsafe { &(*callbacks_ptr).fault };
+    if StreamFault::load(fault).is_some() {
+        return;
+    }
+    if let Some(ref mut f) = unsafe { &mut (*callbacks_ptr).overflow } {
+        let input = InStream::from_callback(raw_in);
+        if panic::catch_unwind(AssertUnwindSafe(|| f(input))).is_err() {
+            StreamFault::OverflowPanicked.store(fault)
+        }
+    }
+}
+
+extern "C" fn in_error_wrapper(raw_in: *mut ffi::SoundIoInStream, error: ffi::enums::SioError) {
+    let callbacks_ptr = unsafe { (*raw_in).userdata as *mut InStreamCallbacks };
+    let fault = unsafe { &(*callbacks_ptr).fault };
+    if StreamFault::load(fault).is_some() {
+        return;
+    }
+    if let Some(ref mut f) = unsafe { &mut (*callbacks_ptr).error } {
+        let input = InStream::from_callback(raw_in);
+        if panic::catch_unwind(AssertUnwindSafe(|| f(input, error))).is_err() {
+            StreamFault::ErrorPanicked.store(fault)
+        }
+    }
+}
+
+type ReadCallback<'a> = Box<FnMut(InStream, u32, u32) + 'a>;
+type OverflowCallback<'a> = Box<FnMut(InStream) + 'a>;
+type InErrorCallback<'a> = Box<FnMut(InStream, ffi::enums::SioError) + 'a>;
+
+struct InStreamCallbacks<'a> {
+    read: Option<ReadCallback<'a>>,
+    overflow: Option<OverflowCallback<'a>>,
+    error: Option<InErrorCallback<'a>>,
+    fault: AtomicUsize,
+}
+impl<'a> Default for InStreamCallbacks<'a> {
+    fn default() -> Self {
+        InStreamCallbacks {
+            read: None,
+            overflow: None,
+            error: None,
+            fault: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// An audio input stream, returned from a `Device`.
+///
+/// The stream can be moved freely, even after it was started,
+/// but it can't outlive the `SoundIo` context of its device.
+/// The `InStream` passed to a callback does not own the stream, registering
+/// callbacks on it has no effect.
+pub struct InStream<'a> {
+    stream: *mut ffi::SoundIoInStream,
+    // `None` if the stream is owned by someone else, i.e. inside of a callback.
+    callbacks: Option<Box<InStreamCallbacks<'a>>>,
+    name: Option<CString>,
+}
+impl<'a> InStream<'a> {
+    pub(crate) fn new(raw_stream: *mut ffi::SoundIoInStream) -> Self {
+        let mut callbacks = Box::new(InStreamCallbacks::default());
+        unsafe {
+            // The callbacks live on the heap, so `userdata` stays valid
+            // even if the stream is moved.
+            (*raw_stream).userdata = &mut *callbacks as *mut InStreamCallbacks as *mut c_void;
+        }
+        InStream {
+            stream: raw_stream,
+            callbacks: Some(callbacks),
+            name: None,
+        }
+    }
+
+    /// Wraps the stream passed to a callback without taking ownership,
+    /// so it is neither destroyed on drop nor does it allocate.
+    fn from_callback(raw_stream: *mut ffi::SoundIoInStream) -> Self {
+        InStream {
+            stream: raw_stream,
+            callbacks: None,
+            name: None,
+        }
+    }
+
+    /// Change settings (e.g. `set_format`) **before** calling `open`.
+    /// After you call this function, `InStream::software_latency` is set to the correct
+    /// value.
+    ///
+    /// The next thing to do is call `start`.
+    /// If this function returns an error, the instream is in an invalid state and
+    /// you must call `destroy` on it.
+    ///
+    /// Possible errors:
+    ///
+    /// - `ffi::enums::SioError::Invalid`
+    ///     - device is not an *input* device
+    ///     - format is not valid
+    ///     - requested layout channel count is greater than 24
+    /// - `ffi::enums::SioError::OpeningDevice`
+    /// - `ffi::enums::SioError::NoMem`
+    /// - `ffi::enums::SioError::BackendDisconnected`
+    /// - `ffi::enums::SioError::SystemResources`
+    /// - `ffi::enums::SioError::NoSuchClient`
+    /// - `ffi::enums::SioError::IncompatibleBackend`
+    /// - `ffi::enums::SioError::IncompatibleDevice`
+    pub fn open(&self) -> SioResult<()> {
+        match unsafe { ffi::soundio_instream_open(self.stream) } {
+            ffi::enums::SioError::None => Ok(()),
+            err => Err(err),
+        }
+    }
+
+    /// After you call this function, the registered `read_callback` will be called.
+    ///
+    /// Possible errors:
+    ///
+    /// - `ffi::enums::SioError::BackendDisconnected`
+    /// - `ffi::enums::SioError::Streaming`
+    /// - `ffi::enums::SioError::OpeningDevice`
+    /// - `ffi::enums::SioError::SystemResources`
+    pub fn start(&self) -> SioResult<()> {
+        match unsafe { ffi::soundio_instream_start(self.stream) } {
+            ffi::enums::SioError::None => Ok(()),
+            err => Err(err),
+        }
+    }
+
+    /// Registers the given callback as `read_callback` that is called as soon as you call `start`.
+    ///
+    /// In this callback, you call `InStream::read_stream_FMT` where `FMT` is one of the supported
+    /// format, `u16`, `f32` etc.
+    ///
+    /// Read at minimum `frame_count_min` frames and at maximum `frame_count_max`
+    /// frames. If you return from the callback without having read
+    /// `frame_count_min`, the frames will be dropped. `frame_count_max` is how
+    /// many frames are available to read.
+    ///
+    /// The code in the supplied function must be suitable for real-time
+    /// execution. That means that it cannot call functions that might block
+    /// for a long time. This includes all I/O functions (disk, TTY, network),
+    /// malloc, free, printf, pthread_mutex_lock, sleep, wait, poll, select,
+    /// pthread_join, pthread_cond_wait, etc.
+    ///
+    /// If the callback panics, the stream drops all captured frames from then on,
+    /// see `InStream::fault`.
+    pub fn register_read_callback<R>(&mut self, callback: R)
+        where R: FnMut(InStream, u32, u32) + 'a
+    {
+        if let Some(ref mut callbacks) = self.callbacks {
+            callbacks.read = Some(Box::new(callback));
+            // register wrapper for read_callback
+            unsafe { (*self.stream).read_callback = Some(read_wrapper) }
+        }
+    }
+
+    /// Registers the given callback as `overflow_callback`.
+    /// This *optional* callback happens when the sound device buffer is full,
+    /// yet there is more captured audio to put in it.
+    /// This is never fired for PulseAudio.
+    /// This is called from the `InStream::read_callback` thread context.
+    pub fn register_overflow_callback<O>(&mut self, callback: O)
+        where O: FnMut(InStream) + 'a
+    {
+        if let Some(ref mut callbacks) = self.callbacks {
+            callbacks.overflow = Some(Box::new(callback));
+            // register wrapper for overflow_callback
+            unsafe { (*self.stream).overflow_callback = Some(overflow_wrapper) }
+        }
+    }
+
+    /// *Optional* callback. `err` is always `ffi::enums::SioError::Streaming`.
+    /// This is an unrecoverable error. The stream is in an
+    /// invalid state and must be destroyed, call `InStream::destroy`.
+    /// If you do not supply `error_callback`, the default callback will print
+    /// a message to stderr and then call `abort`.
+    /// This is called from the `InStream::read_callback` thread context.
+    pub fn register_error_callback<E>(&mut self, callback: E)
+        where E: FnMut(InStream, ffi::enums::SioError) + 'a
+    {
+        if let Some(ref mut callbacks) = self.callbacks {
+            callbacks.error = Some(Box::new(callback));
+            // register wrapper for error_callback
+            unsafe { (*self.stream).error_callback = Some(in_error_wrapper) }
+        }
+    }
+
+    read_stream!(read_stream_i8, i8);
+    read_stream!(read_stream_u8, u8);
+    read_stream!(read_stream_i16, i16);
+    read_stream!(read_stream_u16, u16);
+    read_stream!(read_stream_i24, I24);
+    read_stream!(read_stream_u24, U24);
+    read_stream!(read_stream_i32, i32);
+    read_stream!(read_stream_u32, u32);
+    read_stream!(read_stream_f32, f32);
+    read_stream!(read_stream_f64, f64);
+
+    fn begin_read(&self,
+                  areas: *mut *mut ffi::SoundIoChannelArea,
+                  frame_count: &c_int)
+                  -> SioResult<u32> {
+        let mut actual_frame_count = *frame_count as c_int;
+        match unsafe {
+            ffi::soundio_instream_begin_read(self.stream,
+                                             areas,
+                                             &mut actual_frame_count as *mut c_int)
+        } {
+            ffi::enums::SioError::None => Ok(actual_frame_count as u32),
+            err => Err(err),
+        }
+    }
+
+    fn end_read(&self) -> Option<ffi::enums::SioError> {
+        match unsafe { ffi::soundio_instream_end_read(self.stream) } {
+            ffi::enums::SioError::None => None,
+            err => Some(err),
+        }
+    }
+
+    /// Drops up to `frame_count` captured frames, errors are ignored.
+    fn drop_frames(&self, frame_count: u32) {
+        let mut frames_left = frame_count as c_int;
+        while frames_left > 0 {
+            let mut raw_areas: *mut ffi::SoundIoChannelArea = ptr::null_mut();
+            let actual_frame_count = match self.begin_read(&mut raw_areas, &frames_left) {
+                Ok(0) | Err(_) => return,
+                Ok(cnt) => cnt as c_int,
+            };
+            if self.end_read().is_some() {
+                return;
+            }
+            frames_left -= actual_frame_count;
+        }
+    }
+
+    /// Returns the `StreamFault` if one of the registered callbacks panicked.
+    /// In this case the stream keeps running but drops all captured frames.
+    pub fn fault(&self) -> Option<StreamFault> {
+        let callbacks_ptr = unsafe { (*self.stream).userdata as *const InStreamCallbacks };
+        StreamFault::load(unsafe { &(*callbacks_ptr).fault })
+    }
+
+    /// If the underyling device supports pausing, this pauses the stream and
+    /// prevents `InStream::read_callback` from being called.
+    /// This function may be called from any thread.
+    /// Pausing when already paused or unpausing when already unpaused has no
+    /// effect and returns `None`.
+    ///
+    /// Possible errors:
+    ///
+    /// - `ffi::enums::SioError::BackendDisconnected`
+    /// - `ffi::enums::SioError::Streaming`
+    /// - `ffi::enums::SioError::IncompatibleDevice` - device does not support
+    ///   pausing/unpausing.
+    pub fn pause(&self) -> Option<ffi::enums::SioError> {
+        self.stream_pause(true)
+    }
+
+    /// Unpauses the stream. See `pause` for more details.
+    ///
+    /// Possible errors:
+    ///
+    /// - `ffi::enums::SioError::BackendDisconnected`
+    /// - `ffi::enums::SioError::Streaming`
+    /// - `ffi::enums::SioError::IncompatibleDevice` - device does not support
+    ///   pausing/unpausing.
+    pub fn unpause(&self) -> Option<ffi::enums::SioError> {
+        self.stream_pause(false)
+    }
+
+    fn stream_pause(&self, pause: bool) -> Option<ffi::enums::SioError> {
+        let pause_c_bool = if pause {
+            1u8
+        } else {
+            0u8
+        };
+
+        match unsafe { ffi::soundio_instream_pause(self.stream, pause_c_bool) } {
+            ffi::enums::SioError::None => None,
+            err => Some(err),
+        }
+    }
+
+    /// Obtain the number of seconds that the next frame of sound being
+    /// captured will take to arrive in the buffer, plus the amount of time that is
+    /// represented in the buffer.
+    /// This includes both software and hardware latency.
+    ///
+    /// This function must be called only from within `InStream::read_callback`.
+    ///
+    /// Possible errors:
+    ///
+    /// - `ffi::enums::SioError::Streaming`
+    pub fn latency(&self) -> SioResult<f64> {
+        let mut latency = 0.0f64;
+        match unsafe {
+            ffi::soundio_instream_get_latency(self.stream, &mut latency as *mut c_double)
+        } {
+            ffi::enums::SioError::None => Ok(latency),
+            err => Err(err),
+        }
+    }
+
+    /// Ignoring hardware latency, this is the *number of seconds* it takes for a
+    /// captured sample to become available for reading.
+    ///
+    /// After you call `open`, this value is replaced with the actual software latency,
+    /// as near to this value as possible.
+    /// A higher value means less CPU usage. Defaults to a large value, potentially
+    /// upwards of 2 seconds.
+    ///
+    /// If the device has unknown software latency min and max values, you may still set this, but
+    /// you might not get the value you requested. For PulseAudio, if you set this value to
+    /// non-default, it sets `PA_STREAM_ADJUST_LATENCY` and is the value used for `fragsize`.
+    ///
+    /// For JACK, this value is always equal to `SoundIoDevice::software_latency_current` of the
+    /// device.
+    pub fn set_latency(&self, latency: f64) {
+        unsafe {
+            (*self.stream).software_latency = latency as c_double;
+        }
+    }
+
+    /// Returns the current `format` or a `ffi::enums::SioError::Invalid` if
+    /// the format is not set.
+    pub fn format(&self) -> SioResult<ffi::enums::SioFormat> {
+        match unsafe { (*self.stream).format } {
+            ffi::enums::SioFormat::Invalid => Err(ffi::enums::SioError::Invalid),
+            fmt => Ok(fmt),
+        }
+    }
+
+    /// Sets the stream format to `format`.
+    /// **Must** be called before `open`ing the stream.
+    ///
+    /// If the device doesn't support the format
+    /// `ffi::enums::SioError::IncompatibleDevice` is returned.
+    pub fn set_format(&self, format: ffi::enums::SioFormat) -> SioResult<()> {
+        let dev = self.device();
+        if dev.supports_format(format) {
+            unsafe { (*self.stream).format = format };
+            Ok(())
         } else {
-            // reset usage marker.
-            self.marker = false
+            Err(ffi::enums::SioError::IncompatibleDevice)
         }
     }
+
+    /// Sets the stream format to the first format of `preferred` that the device supports,
+    /// or to the best supported format, see `Device::negotiate_format`, and returns it.
+    /// **Must** be called before `open`ing the stream.
+    ///
+    /// Samples are converted from the negotiated format when they are read,
+    /// so the application can stick to its own sample type, see `Sample`.
+    /// Pass `&[S::format()]` to avoid the conversion if possible.
+    ///
+    /// If the supported formats of the device are unknown
+    /// `ffi::enums::SioError::IncompatibleDevice` is returned.
+    pub fn negotiate_format(&self,
+                            preferred: &[ffi::enums::SioFormat])
+                            -> SioResult<ffi::enums::SioFormat> {
+        let format = try!(self.device()
+                              .negotiate_format(preferred)
+                              .ok_or(ffi::enums::SioError::IncompatibleDevice));
+        unsafe { (*self.stream).format = format };
+        Ok(format)
+    }
+
+    /// Returns the channel layout of the input stream.
+    pub fn layout(&self) -> ChannelLayout {
+        ChannelLayout::new(unsafe { &(*self.stream).layout })
+    }
+
+    // Doesn't copy the layout, so it can be used within the callbacks.
+    fn channel_count(&self) -> u32 {
+        unsafe { (*self.stream).layout.channel_count as u32 }
+    }
+
+    /// Sets the channel layout of the stream to `layout`.
+    /// **Must** be called before `open`ing the stream,
+    /// check `layout_error` afterwards.
+    ///
+    /// If the device doesn't support the layout
+    /// `ffi::enums::SioError::IncompatibleDevice` is returned.
+    pub fn set_layout(&self, layout: &ChannelLayout) -> SioResult<()> {
+        if self.device().supports_layout(layout) {
+            unsafe { (*self.stream).layout = layout.to_raw() };
+            Ok(())
+        } else {
+            Err(ffi::enums::SioError::IncompatibleDevice)
+        }
+    }
+
+    /// Sets the channel layout to the default layout for `channel_count` channels,
+    /// see `ChannelLayout::default` and `set_layout`.
+    ///
+    /// Possible errors:
+    ///
+    /// - `ffi::enums::SioError::Invalid` - there is no default layout for `channel_count`
+    /// - `ffi::enums::SioError::IncompatibleDevice` - the device doesn't support the layout
+    pub fn set_channel_count(&self, channel_count: u32) -> SioResult<()> {
+        let layout = try!(ChannelLayout::default(channel_count)
+                              .ok_or(ffi::enums::SioError::Invalid));
+        self.set_layout(&layout)
+    }
+
+    /// Returns the sample rate of the input stream.
+    pub fn sample_rate(&self) -> u32 {
+        unsafe { (*self.stream).sample_rate as u32 }
+    }
+
+    /// Sets the stream sample rate.
+    /// Make sure that the device supports the given sample rate to avoid
+    /// sample rate conversions. A `Device` provides `supports_sample_rate` and
+    /// `nearest_sample_rate` methods for this purpose.
+    pub fn set_sample_rate(&self, sample_rate: u32) {
+        unsafe { (*self.stream).sample_rate = sample_rate as c_int }
+    }
+
+    /// Returns the underlying device of the input stream.
+    pub fn device(&self) -> Device<'a> {
+        Device::with_ref(unsafe { (*self.stream).device })
+    }
+
+    /// Sets the stream name to `name`.
+    /// PulseAudio uses this for the stream name.
+    /// JACK uses this for the client name of the client that connects when you
+    /// open the stream.
+    /// WASAPI uses this for the session display name.
+    /// Colons (`:`) contained in `name` will be replaced with `_`.
+    /// If the `name` contains a `NULL` byte, `SioError::EncodingString` is returned.
+    /// Returns `SioError::Invalid` if called on a stream passed to a callback.
+    pub fn set_name<T: Into<String>>(&mut self, name: T) -> SioResult<()> {
+        if self.callbacks.is_none() {
+            return Err(ffi::enums::SioError::Invalid);
+        }
+        let s = name.into().replace(":", "_");
+        let name = try!(CString::new(s).map_err(|_| ffi::enums::SioError::EncodingString));
+        unsafe { (*self.stream).name = name.as_ptr() };
+        self.name = Some(name);
+        Ok(())
+    }
+
+    /// Returns the stream name or `None` if the name wasn't set.
+    pub fn name(&self) -> Option<String> {
+        let s_ptr = unsafe { (*self.stream).name };
+        if !s_ptr.is_null() {
+            ffi::utils::ptr_to_string(s_ptr).ok()
+        } else {
+            None
+        }
+    }
+
+    /// Returns an `ffi::enums::SioError` if the layout is incompatible
+    /// with the audio input device.
+    /// If the layout is compatible `()` is returned.
+    pub fn layout_error(&self) -> SioResult<()> {
+        match unsafe { (*self.stream).layout_error } {
+            ffi::enums::SioError::None => Ok(()),
+            err => Err(err),
+        }
+    }
+
+    /// Destroys the input stream.
+    /// Calls this when your application shuts down.
+    fn destroy(&self) {
+        unsafe { ffi::soundio_instream_destroy(self.stream) }
+    }
+}
+impl<'a> Drop for InStream<'a> {
+    fn drop(&mut self) {
+        // Streams passed to a callback are owned by someone else.
+        if self.callbacks.is_some() {
+            self.destroy()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mixed_frames() {
+        let mut mixer = ChannelMixer::silent(3, 2);
+        mixer.set_gain(0, 0, 1.0);
+        mixer.set_gain(1, 0, 0.5);
+        mixer.set_gain(1, 1, 0.5);
+        mixer.set_gain(2, 1, -1.0);
+        let frames = [1.0, 0.5, 0.25, -1.0, 0.0, 1.0];
+        let mixed: Vec<_> = mixed_frames(&mixer, &frames).unwrap().collect();
+        assert_eq!(mixed.len(), 2);
+        assert_eq!(&mixed[0][..2], &[1.25, 0.0]);
+        assert_eq!(&mixed[1][..2], &[-1.0, -1.0]);
+        // the samples beyond the output channels are silence
+        assert!(mixed.iter().all(|frame| frame[2..].iter().all(|&sample| sample == 0.0)));
+    }
+
+    #[test]
+    fn test_mixed_frames_invalid() {
+        let mixer = ChannelMixer::silent(2, 2);
+        assert_eq!(mixed_frames(&mixer, &[0.0; 3]).err(), Some(ffi::enums::SioError::Invalid));
+        let empty = ChannelMixer::silent(0, 2);
+        assert_eq!(mixed_frames(&empty, &[]).err(), Some(ffi::enums::SioError::Invalid));
+        // libsoundio supports up to 24 channels
+        let wide = ChannelMixer::silent(1, MAX_CHANNELS as usize + 1);
+        assert_eq!(mixed_frames(&wide, &[0.0]).err(), Some(ffi::enums::SioError::Invalid));
+        let widest = ChannelMixer::silent(1, MAX_CHANNELS as usize);
+        assert_eq!(mixed_frames(&widest, &[0.0]).unwrap().count(), 1);
+    }
 }
@@ -0,0 +1,204 @@
+use ffi::enums::{SioDeviceAim, SioError};
+use base::{Device, SoundIo};
+
+/// The properties of a `Device` that are tracked by a `DeviceWatcher`.
+///
+/// A device is identified by its `id`, `aim` and `is_raw`,
+/// the index of a device may change between two snapshots.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceInfo {
+    pub id: String,
+    pub name: String,
+    pub aim: SioDeviceAim,
+    pub is_raw: bool,
+    pub probe_error: Option<SioError>,
+}
+impl DeviceInfo {
+    /// Copies the properties of `device`, an id or name that is not valid UTF-8 is left empty.
+    pub fn new(device: &Device) -> DeviceInfo {
+        DeviceInfo {
+            id: device.id().unwrap_or_default(),
+            name: device.name().unwrap_or_default(),
+            aim: device.aim(),
+            is_raw: device.is_raw(),
+            probe_error: device.probe_error(),
+        }
+    }
+
+    /// Returns `true` if both infos describe the same device.
+    pub fn is_same_device(&self, other: &DeviceInfo) -> bool {
+        self.id == other.id && self.aim == other.aim && self.is_raw == other.is_raw
+    }
+}
+
+/// The devices of a `SoundIo` context at the time of the last `flush_events`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeviceSnapshot {
+    pub inputs: Vec<DeviceInfo>,
+    pub outputs: Vec<DeviceInfo>,
+    /// Index of the default device in `inputs`.
+    pub default_input: Option<usize>,
+    /// Index of the default device in `outputs`.
+    pub default_output: Option<usize>,
+}
+impl DeviceSnapshot {
+    /// Returns the default input device.
+    pub fn default_input_device(&self) -> Option<&DeviceInfo> {
+        self.default_input.and_then(|idx| self.inputs.get(idx))
+    }
+
+    /// Returns the default output device.
+    pub fn default_output_device(&self) -> Option<&DeviceInfo> {
+        self.default_output.and_then(|idx| self.outputs.get(idx))
+    }
+}
+
+/// Provides the device snapshots compared by a `DeviceWatcher`.
+///
+/// It is implemented for `SoundIo` and can be implemented by a mock to test device changes.
+pub trait SnapshotSource {
+    fn snapshot(&self) -> DeviceSnapshot;
+}
+impl SnapshotSource for SoundIo {
+    /// Returns an empty snapshot if you never called `flush_events`.
+    fn snapshot(&self) -> DeviceSnapshot {
+        let inputs = (0..self.input_device_count().unwrap_or(0))
+                         .filter_map(|idx| self.input_device(idx))
+                         .map(|dev| DeviceInfo::new(&dev))
+                         .collect();
+        let outputs = (0..self.output_device_count().unwrap_or(0))
+                          .filter_map(|idx| self.output_device(idx))
+                          .map(|dev| DeviceInfo::new(&dev))
+                          .collect();
+        DeviceSnapshot {
+            inputs: inputs,
+            outputs: outputs,
+            default_input: self.default_input_device_index().map(|idx| idx as usize),
+            default_output: self.default_output_device_index().map(|idx| idx as usize),
+        }
+    }
+}
+
+/// A change between two device snapshots, reported by `DeviceWatcher`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeviceEvent {
+    InputAdded(DeviceInfo),
+    InputRemoved(DeviceInfo),
+    OutputAdded(DeviceInfo),
+    OutputRemoved(DeviceInfo),
+    /// The new default input device, `None` if there is none.
+    DefaultInputChanged(Option<DeviceInfo>),
+    /// The new default output device, `None` if there is none.
+    DefaultOutputChanged(Option<DeviceInfo>),
+    /// The device with the new `probe_error`, e.g. after `SoundIo::force_device_scan`.
+    ProbeErrorChanged(DeviceInfo),
+}
+
+/// Compares consecutive device snapshots and reports the differences as `DeviceEvent`s.
+///
+/// Devices are matched by id instead of their index, which may change between snapshots.
+///
+/// # Example
+///
+/// ```
+/// extern crate rsoundio;
+///
+/// let sio = rsoundio::SoundIo::default();
+/// sio.connect().unwrap();
+/// let mut watcher = rsoundio::DeviceWatcher::new();
+/// sio.flush_events();
+/// // the first poll reports all devices as added
+/// for event in watcher.poll(&sio) {
+///     println!("{:?}", event);
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct DeviceWatcher {
+    snapshot: DeviceSnapshot,
+}
+impl DeviceWatcher {
+    /// Creates a watcher that starts with an empty snapshot.
+    pub fn new() -> DeviceWatcher {
+        DeviceWatcher::default()
+    }
+
+    /// Creates a watcher that starts with `snapshot`, e.g. to ignore the devices present on startup.
+    pub fn with_snapshot(snapshot: DeviceSnapshot) -> DeviceWatcher {
+        DeviceWatcher { snapshot: snapshot }
+    }
+
+    /// Returns the last snapshot.
+    pub fn snapshot(&self) -> &DeviceSnapshot {
+        &self.snapshot
+    }
+
+    /// Takes a new snapshot from `source` and returns the changes since the last one.
+    /// Call `SoundIo::flush_events` or `SoundIo::wait_events` before to update the devices.
+    pub fn poll<S: SnapshotSource + ?Sized>(&mut self, source: &S) -> Vec<DeviceEvent> {
+        self.update(source.snapshot())
+    }
+
+    /// Replaces the last snapshot with `snapshot` and returns the changes.
+    ///
+    /// The input and then the output devices are reported in the order removed,
+    /// added and changed probe error, followed by the changed default devices.
+    pub fn update(&mut self, snapshot: DeviceSnapshot) -> Vec<DeviceEvent> {
+        let mut events = vec![];
+        diff_devices(&self.snapshot.inputs,
+                     &snapshot.inputs,
+                     DeviceEvent::InputAdded,
+                     DeviceEvent::InputRemoved,
+                     &mut events);
+        diff_devices(&self.snapshot.outputs,
+                     &snapshot.outputs,
+                     DeviceEvent::OutputAdded,
+                     DeviceEvent::OutputRemoved,
+                     &mut events);
+        if default_changed(self.snapshot.default_input_device(),
+                           snapshot.default_input_device()) {
+            events.push(DeviceEvent::DefaultInputChanged(snapshot.default_input_device()
+                                                                 .cloned()));
+        }
+        if default_changed(self.snapshot.default_output_device(),
+                           snapshot.default_output_device()) {
+            events.push(DeviceEvent::DefaultOutputChanged(snapshot.default_output_device()
+                                                                  .cloned()));
+        }
+        self.snapshot = snapshot;
+        events
+    }
+}
+
+fn find_device<'a>(devices: &'a [DeviceInfo], device: &DeviceInfo) -> Option<&'a DeviceInfo> {
+    devices.iter().find(|other| other.is_same_device(device))
+}
+
+fn diff_devices<A, R>(old: &[DeviceInfo],
+                      new: &[DeviceInfo],
+                      added: A,
+                      removed: R,
+                      events: &mut Vec<DeviceEvent>)
+    where A: Fn(DeviceInfo) -> DeviceEvent,
+          R: Fn(DeviceInfo) -> DeviceEvent
+{
+    events.extend(old.iter()
+                     .filter(|dev| find_device(new, dev).is_none())
+                     .map(|dev| removed(dev.clone())));
+    events.extend(new.iter()
+                     .filter(|dev| find_device(old, dev).is_none())
+                     .map(|dev| added(dev.clone())));
+    events.extend(new.iter()
+                     .filter(|dev| match find_device(old, dev) {
+                         Some(old) => old.probe_error != dev.probe_error,
+                         None => false,
+                     })
+                     .map(|dev| DeviceEvent::ProbeErrorChanged(dev.clone())));
+}
+
+fn default_changed(old: Option<&DeviceInfo>, new: Option<&DeviceInfo>) -> bool {
+    match (old, new) {
+        (Some(old), Some(new)) => !old.is_same_device(new),
+        (None, None) => false,
+        _ => true,
+    }
+}
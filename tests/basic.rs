@@ -1,7 +1,10 @@
 extern crate rsoundio;
 extern crate rci;
+#[cfg(feature = "regex")]
+extern crate regex;
 
 use rci::Ci;
+use rsoundio::Sample;
 
 #[test]
 fn test_soundio() {
@@ -48,6 +51,235 @@ fn test_channel_layout() {
 
 }
 
+#[test]
+fn test_custom_channel_layout() {
+    use std::collections::HashSet;
+    use rsoundio::SioChannelId;
+    let stereo = rsoundio::ChannelLayout::from_channels(&[SioChannelId::FrontLeft,
+                                                          SioChannelId::FrontRight])
+                     .unwrap();
+    assert_eq!(stereo, rsoundio::ChannelLayout::default(2).unwrap());
+    assert_eq!(stereo.name(), Some("Stereo"));
+    let mut custom = rsoundio::ChannelLayout::from_channels(&[SioChannelId::Aux0,
+                                                              SioChannelId::Aux1,
+                                                              SioChannelId::Aux2])
+                         .unwrap();
+    assert_eq!(custom.name(), None);
+    assert_eq!(custom.channel_count(), 3);
+    assert_eq!(custom.find_channel(SioChannelId::Aux2), Some(2));
+    assert!(!custom.detect_builtin());
+    custom.set_name("Aux");
+    assert_eq!(format!("{}", custom), "Aux");
+    assert!(rsoundio::ChannelLayout::from_channels(&[SioChannelId::Aux0; 25]).is_none());
+    let mut layouts = HashSet::new();
+    layouts.insert(stereo.clone());
+    layouts.insert(custom.clone());
+    layouts.insert(rsoundio::ChannelLayout::default(2).unwrap());
+    assert_eq!(layouts.len(), 2);
+    // layouts outlive the context they were returned from
+    let current_layout = {
+        let sio = rsoundio::SoundIo::default();
+        sio.connect_backend(rsoundio::SioBackend::Dummy).unwrap();
+        sio.flush_events();
+        let dev = sio.default_output_device().unwrap();
+        assert!(!dev.supports_layout(&custom) || dev.layouts().contains(&custom));
+        dev.current_layout()
+    };
+    assert!(current_layout.channel_count() > 0);
+}
+
+#[test]
+fn test_channel_layout_ids() {
+    use rsoundio::{ChannelLayout, SioChannelId, SioChannelLayoutId};
+    let surround = ChannelLayout::from_id(SioChannelLayoutId::FivePointOne);
+    assert_eq!(surround.channel_count(), 6);
+    assert_eq!(surround.id(), Some(SioChannelLayoutId::FivePointOne));
+    assert_eq!(ChannelLayout::from_id(SioChannelLayoutId::Octagonal).id(),
+               Some(SioChannelLayoutId::Octagonal));
+    assert_eq!(ChannelLayout::from_channels(&[SioChannelId::Aux0]).unwrap().id(), None);
+    assert_eq!("5.1".parse::<ChannelLayout>(), Ok(surround.clone()));
+    assert_eq!(" stereo ".parse::<ChannelLayout>(),
+               Ok(ChannelLayout::from_id(SioChannelLayoutId::Stereo)));
+    let parsed: ChannelLayout = "FL,FR,FC,LFE,SL,SR".parse().unwrap();
+    assert_eq!(parsed.channels(),
+               &[SioChannelId::FrontLeft,
+                 SioChannelId::FrontRight,
+                 SioChannelId::FrontCenter,
+                 SioChannelId::Lfe,
+                 SioChannelId::SideLeft,
+                 SioChannelId::SideRight]);
+    assert_eq!(parsed.id().is_some(), parsed.name().is_some());
+    let mono: ChannelLayout = "Front Center".parse().unwrap();
+    assert_eq!(mono.id(), Some(SioChannelLayoutId::Mono));
+    assert_eq!("FL,Nowhere".parse::<ChannelLayout>(),
+               Err(rsoundio::SioError::Invalid));
+    assert_eq!("".parse::<ChannelLayout>(), Err(rsoundio::SioError::Invalid));
+}
+
+#[test]
+fn test_channel_mixer() {
+    use std::f32::consts::FRAC_1_SQRT_2;
+    use rsoundio::{ChannelLayout, ChannelMixer, MixOptions, SioChannelLayoutId};
+    let stereo = ChannelLayout::from_id(SioChannelLayoutId::Stereo);
+    let surround = ChannelLayout::from_id(SioChannelLayoutId::FivePointOne);
+    // FL, FR, FC, LFE, SL, SR to FL, FR
+    let downmix = ChannelMixer::new(&surround, &stereo);
+    assert_eq!((downmix.input_count(), downmix.output_count()), (6, 2));
+    assert_eq!(downmix.gain(0, 0), 1.0);
+    assert_eq!(downmix.gain(1, 0), 0.0);
+    assert_eq!(downmix.gain(2, 0), FRAC_1_SQRT_2);
+    assert_eq!(downmix.gain(2, 1), FRAC_1_SQRT_2);
+    assert_eq!(downmix.gain(3, 0), 0.0);
+    assert_eq!(downmix.gain(4, 0), FRAC_1_SQRT_2);
+    assert_eq!(downmix.gain(5, 1), FRAC_1_SQRT_2);
+    let mut out = [0.0; 2];
+    downmix.mix(&[0.5, 0.0, 0.0, 1.0, 0.0, 0.0], &mut out);
+    assert_eq!(out, [0.5, 0.0]);
+    let options = MixOptions {
+        lfe_gain: 0.5,
+        center_gain: 1.0,
+        normalize: true,
+        ..MixOptions::default()
+    };
+    let downmix = ChannelMixer::with_options(&surround, &stereo, options);
+    assert!(downmix.gain(3, 0) > 0.0);
+    let row_sum: f32 = (0..6).map(|idx| downmix.gain(idx, 0)).sum();
+    assert!((row_sum - 1.0).abs() < 1e-6);
+    // stereo is copied into the front channels of 5.1
+    let upmix = ChannelMixer::new(&stereo, &surround);
+    let mut out = [1.0; 12];
+    assert_eq!(upmix.mix_interleaved(&[0.25, -0.25, 0.5, -0.5, 1.0], &mut out), 2);
+    assert_eq!(&out[..6], &[0.25, -0.25, 0.0, 0.0, 0.0, 0.0]);
+    assert_eq!(&out[6..], &[0.5, -0.5, 0.0, 0.0, 0.0, 0.0]);
+    let mono = ChannelLayout::from_id(SioChannelLayoutId::Mono);
+    let mut out = [0.0; 1];
+    ChannelMixer::new(&stereo, &mono).mix(&[1.0, 1.0], &mut out);
+    assert!((out[0] - 2.0 * FRAC_1_SQRT_2).abs() < 1e-6);
+}
+
+#[test]
+fn test_channel_routing() {
+    use rsoundio::{ChannelLayout, ChannelRouting, SioChannelId};
+    let layout = ChannelLayout::from_channels(&[SioChannelId::FrontLeft,
+                                                SioChannelId::FrontRight,
+                                                SioChannelId::Aux0,
+                                                SioChannelId::Aux1,
+                                                SioChannelId::ClickTrack])
+                     .unwrap();
+    let mut routing = ChannelRouting::new(3, &layout);
+    routing.route(0, SioChannelId::FrontLeft, 1.0).unwrap();
+    routing.route(0, SioChannelId::FrontRight, 1.0).unwrap();
+    routing.route(1, 2, 0.5).unwrap();
+    routing.route(1, 3, 0.5).unwrap();
+    routing.route(2, SioChannelId::ClickTrack, 1.0).unwrap();
+    assert_eq!(routing.route(3, 0, 1.0), Err(rsoundio::SioError::Invalid));
+    assert_eq!(routing.route(0, 5, 1.0), Err(rsoundio::SioError::Invalid));
+    assert_eq!(routing.route(0, SioChannelId::Lfe, 1.0),
+               Err(rsoundio::SioError::Invalid));
+    // the mixer holds one gain per route, all other gains are zero
+    let mixer = routing.mixer();
+    assert_eq!(mixer.input_count(), 3);
+    assert_eq!(mixer.output_count(), 5);
+    let gains = |input| (0..5).map(|output| mixer.gain(input, output)).collect::<Vec<f32>>();
+    assert_eq!(gains(0), [1.0, 1.0, 0.0, 0.0, 0.0]);
+    assert_eq!(gains(1), [0.0, 0.0, 0.5, 0.5, 0.0]);
+    assert_eq!(gains(2), [0.0, 0.0, 0.0, 0.0, 1.0]);
+    let mut out = [0.0; 5];
+    routing.mixer().mix(&[0.25, 1.0, -1.0], &mut out);
+    assert_eq!(out, [0.25, 0.25, 0.5, 0.5, -1.0]);
+    // a route is replaced, not added up
+    routing.route(1, 2, 0.0).unwrap();
+    assert_eq!(routing.mixer().gain(1, 2), 0.0);
+    routing.mixer().mix(&[0.25, 1.0, -1.0], &mut out);
+    assert_eq!(out, [0.25, 0.25, 0.0, 0.5, -1.0]);
+    routing.clear();
+    assert_eq!(routing.mixer(), &rsoundio::ChannelMixer::silent(3, 5));
+    routing.mixer().mix(&[0.25, 1.0, -1.0], &mut out);
+    assert_eq!(out, [0.0; 5]);
+    assert_eq!(routing.layout(), &layout);
+}
+
+#[test]
+fn test_ambisonic_decoder() {
+    use rsoundio::{ChannelLayout, ChannelMixer, SioChannelId, SioChannelLayoutId};
+    let b_format = ChannelLayout::from_channels(&[SioChannelId::AmbisonicW,
+                                                  SioChannelId::AmbisonicX,
+                                                  SioChannelId::AmbisonicY,
+                                                  SioChannelId::AmbisonicZ])
+                       .unwrap();
+    let front = [::std::f32::consts::FRAC_1_SQRT_2, 1.0, 0.0, 0.0];
+    let left = [::std::f32::consts::FRAC_1_SQRT_2, 0.0, 1.0, 0.0];
+    let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+
+    let octagonal = ChannelLayout::from_id(SioChannelLayoutId::Octagonal);
+    let decoder = ChannelMixer::ambisonic_decoder(&b_format, &octagonal).unwrap();
+    let mut out = vec![0.0; octagonal.channel_count() as usize];
+    decoder.mix(&front, &mut out);
+    // the pressure of the source is preserved and the front speakers are loudest
+    assert!(close(out.iter().sum::<f32>(), 1.0));
+    let fc = octagonal.find_channel(SioChannelId::FrontCenter).unwrap() as usize;
+    let bc = octagonal.find_channel(SioChannelId::BackCenter).unwrap() as usize;
+    assert!(out.iter().all(|&s| s <= out[fc]));
+    assert!(out.iter().all(|&s| s >= out[bc]));
+
+    let surround = ChannelLayout::from_id(SioChannelLayoutId::FivePointOne);
+    let decoder = ChannelMixer::ambisonic_decoder(&b_format, &surround).unwrap();
+    let mut out = vec![0.0; surround.channel_count() as usize];
+    decoder.mix(&left, &mut out);
+    let fl = surround.find_channel(SioChannelId::FrontLeft).unwrap() as usize;
+    let fr = surround.find_channel(SioChannelId::FrontRight).unwrap() as usize;
+    let lfe = surround.find_channel(SioChannelId::Lfe).unwrap() as usize;
+    assert!(out[fl] > out[fr]);
+    assert_eq!(out[lfe], 0.0);
+
+    let stereo = ChannelLayout::from_id(SioChannelLayoutId::Stereo);
+    let decoder = ChannelMixer::ambisonic_decoder(&b_format, &stereo).unwrap();
+    let mut out = [0.0; 2];
+    decoder.mix(&front, &mut out);
+    assert!(close(out[0], 0.5) && close(out[1], 0.5));
+    decoder.mix(&left, &mut out);
+    assert!(close(out[0], 1.0) && close(out[1], 0.0));
+
+    assert_eq!(ChannelMixer::ambisonic_decoder(&stereo, &stereo).err(),
+               Some(rsoundio::SioError::Invalid));
+}
+
+#[test]
+fn test_stereo_mixers() {
+    use rsoundio::{ChannelLayout, ChannelMixer, SioChannelId, SioChannelLayoutId};
+    let stereo = ChannelLayout::from_id(SioChannelLayoutId::Stereo);
+    let mid_side = ChannelLayout::from_channels(&[SioChannelId::MsMid, SioChannelId::MsSide])
+                       .unwrap();
+    let xy = ChannelLayout::from_channels(&[SioChannelId::XyX, SioChannelId::XyY]).unwrap();
+
+    let encoder = ChannelMixer::stereo_encoder(&stereo, &mid_side, 1.0).unwrap();
+    let decoder = ChannelMixer::stereo_decoder(&mid_side, &stereo, 1.0).unwrap();
+    let (mut encoded, mut decoded) = ([0.0; 2], [0.0; 2]);
+    encoder.mix(&[1.0, 0.5], &mut encoded);
+    assert_eq!(encoded, [0.75, 0.25]);
+    decoder.mix(&encoded, &mut decoded);
+    assert_eq!(decoded, [1.0, 0.5]);
+    // no side signal is mono
+    let decoder = ChannelMixer::stereo_decoder(&mid_side, &stereo, 0.0).unwrap();
+    decoder.mix(&encoded, &mut decoded);
+    assert_eq!(decoded, [0.75, 0.75]);
+
+    let decoder = ChannelMixer::stereo_decoder(&xy, &stereo, 1.0).unwrap();
+    decoder.mix(&[1.0, 0.5], &mut decoded);
+    assert_eq!(decoded, [1.0, 0.5]);
+    let decoder = ChannelMixer::stereo_decoder(&xy, &stereo, 2.0).unwrap();
+    decoder.mix(&[1.0, 0.5], &mut decoded);
+    assert_eq!(decoded, [1.25, 0.25]);
+    let encoder = ChannelMixer::stereo_encoder(&stereo, &xy, 0.0).unwrap();
+    encoder.mix(&[1.0, 0.5], &mut encoded);
+    assert_eq!(encoded, [0.75, 0.75]);
+
+    assert_eq!(ChannelMixer::stereo_decoder(&stereo, &stereo, 1.0).err(),
+               Some(rsoundio::SioError::Invalid));
+    assert_eq!(ChannelMixer::stereo_encoder(&mid_side, &mid_side, 1.0).err(),
+               Some(rsoundio::SioError::Invalid));
+}
+
 #[test]
 fn test_enums() {
     assert_eq!("(no error)", format!("{}", rsoundio::SioError::None));
@@ -63,6 +295,58 @@ fn test_enums() {
     assert_eq!(4, rsoundio::SioFormat::U32LE.bytes_per_sample());
 }
 
+#[test]
+fn test_sample_conversion() {
+    assert_eq!(i16::from_f64(1.0), i16::MAX);
+    assert_eq!(i16::from_f64(-1.0), i16::MIN);
+    assert_eq!(i16::from_f64(0.5), 16384);
+    assert_eq!(u8::from_f64(0.0), 128);
+    assert_eq!(u8::from_f64(-1.0), 0);
+    assert_eq!(u16::from_f64(1.0), u16::MAX);
+    assert_eq!(i32::from_f64(-1.0), i32::MIN);
+    assert_eq!(u32::from_f64(0.0), 1 << 31);
+    // out of range values are clamped
+    assert_eq!(i8::from_f64(2.5), i8::MAX);
+    assert_eq!(f32::from_f64(-3.0), -1.0);
+    assert_eq!(f64::from_f64(f64::NAN), 0.0);
+    assert_eq!(i16::MIN.to_f64(), -1.0);
+    assert_eq!(128u8.to_f64(), 0.0);
+    assert_eq!(0u32.to_f64(), -1.0);
+    assert_eq!(i16::from_f64(0.25f32.to_f64()), 8192);
+    assert_eq!(u16::from_f64((-16384i16).to_f64()), 16384);
+    if cfg!(target_endian = "little") {
+        assert_eq!(f32::format(), rsoundio::SioFormat::Float32LE);
+        assert_eq!(u16::format(), rsoundio::SioFormat::U16LE);
+    } else {
+        assert_eq!(f32::format(), rsoundio::SioFormat::Float32BE);
+        assert_eq!(u16::format(), rsoundio::SioFormat::U16BE);
+    }
+    assert_eq!(i8::format(), rsoundio::SioFormat::S8);
+}
+
+#[test]
+fn test_24_bit_samples() {
+    use rsoundio::{I24, U24};
+    assert_eq!(I24::new(1 << 23), None);
+    assert_eq!(I24::new(-(1 << 23)), Some(I24::min_value()));
+    assert_eq!(U24::new(1 << 24), None);
+    assert_eq!(I24::new(-1).unwrap().get(), -1);
+    assert_eq!(I24::from_f64(1.0), I24::max_value());
+    assert_eq!(I24::from_f64(-1.0), I24::min_value());
+    assert_eq!(i32::from(I24::from_f64(0.5)), 1 << 22);
+    assert_eq!(U24::from_f64(0.0), U24::new(1 << 23).unwrap());
+    assert_eq!(U24::from_f64(-1.0), U24::min_value());
+    assert_eq!(u32::from(U24::from_f64(1.0)), (1 << 24) - 1);
+    assert_eq!(I24::min_value().to_f64(), -1.0);
+    assert_eq!(U24::new(1 << 23).unwrap().to_f64(), 0.0);
+    assert_eq!(i16::from_f64(I24::from_f64(-0.25).to_f64()), -8192);
+    if cfg!(target_endian = "little") {
+        assert_eq!(I24::format(), rsoundio::SioFormat::S24LE);
+    } else {
+        assert_eq!(I24::format(), rsoundio::SioFormat::S24BE);
+    }
+}
+
 #[test]
 fn test_device() {
     let sio = rsoundio::SoundIo::default();
@@ -87,3 +371,384 @@ fn test_device() {
     assert!(in_dev.nearest_sample_rate(1) > 0);
     assert!(out_dev.nearest_sample_rate(1) > 0);
 }
+
+#[test]
+fn test_negotiate_format() {
+    let sio = rsoundio::SoundIo::default();
+    sio.connect_backend(rsoundio::SioBackend::Dummy).unwrap();
+    sio.flush_events();
+    let dev = sio.default_output_device().unwrap();
+    let formats = dev.formats().to_vec();
+    assert!(!formats.is_empty());
+    // the first supported preferred format wins
+    let preferred = [rsoundio::SioFormat::Invalid, formats[formats.len() - 1]];
+    assert_eq!(dev.negotiate_format(&preferred), Some(formats[formats.len() - 1]));
+    // otherwise native-endian float is chosen
+    let format = dev.negotiate_format(&[rsoundio::SioFormat::Invalid]).unwrap();
+    assert!(formats.contains(&format));
+    if formats.contains(&f32::format()) {
+        assert_eq!(format, f32::format());
+    }
+    let stream = dev.create_outstream().unwrap();
+    assert_eq!(stream.negotiate_format(&[]), Ok(format));
+    assert_eq!(stream.format(), Ok(format));
+}
+
+#[test]
+fn test_soundio_callbacks() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let mut sio = rsoundio::SoundIo::default();
+    let changes = Rc::new(Cell::new(0));
+    let c = changes.clone();
+    sio.register_devices_change_callback(move || c.set(c.get() + 1));
+    sio.register_backend_disconnect_callback(|err: rsoundio::SioError| {
+        panic!("Backend disconnected: {}", err)
+    });
+    let signals = Arc::new(AtomicUsize::new(0));
+    let s = signals.clone();
+    sio.register_events_signal_callback(move || {
+        s.fetch_add(1, Ordering::SeqCst);
+    });
+    // moving the context must not invalidate the registered callbacks
+    let boxed = Box::new(sio);
+    boxed.connect_backend(rsoundio::SioBackend::Dummy).unwrap();
+    boxed.flush_events();
+    assert!(changes.get() > 0);
+    let signals_before = signals.load(Ordering::SeqCst);
+    boxed.wakeup();
+    assert!(signals.load(Ordering::SeqCst) > signals_before);
+    boxed.flush_events();
+    // the Dummy backend emits its device list only once
+    assert_eq!(changes.get(), 1);
+}
+
+#[test]
+fn test_device_iterators() {
+    let sio = rsoundio::SoundIo::default();
+    sio.connect_backend(rsoundio::SioBackend::Dummy).unwrap();
+    assert_eq!(sio.output_devices().err(), Some(rsoundio::SioError::Invalid));
+    sio.flush_events();
+    let outputs: Vec<_> = sio.output_devices().unwrap().collect();
+    assert_eq!(outputs.len(), sio.output_device_count().unwrap() as usize);
+    assert!(outputs.iter().all(|dev| dev.aim() == rsoundio::SioDeviceAim::Output));
+    let defaults: Vec<_> = outputs.iter().filter(|dev| dev.is_default()).collect();
+    assert_eq!(defaults.len(), 1);
+    assert!(*defaults[0] == sio.default_output_device().unwrap());
+    let inputs = sio.devices(rsoundio::SioDeviceAim::Input).unwrap();
+    assert_eq!(inputs.count(), sio.input_device_count().unwrap() as usize);
+    assert_eq!(sio.input_devices().unwrap().filter(|dev| dev.is_default()).count(), 1);
+}
+
+#[test]
+fn test_devices_before_flush_events() {
+    let sio = rsoundio::SoundIo::default();
+    sio.connect_backend(rsoundio::SioBackend::Dummy).unwrap();
+    // there is no device snapshot yet
+    assert_eq!(sio.output_devices().err(), Some(rsoundio::SioError::Invalid));
+    assert_eq!(sio.input_devices().err(), Some(rsoundio::SioError::Invalid));
+    assert_eq!(sio.devices(rsoundio::SioDeviceAim::Input).err(),
+               Some(rsoundio::SioError::Invalid));
+    sio.flush_events();
+    assert!(sio.output_devices().unwrap().count() > 0);
+    assert!(sio.input_devices().unwrap().count() > 0);
+}
+
+#[test]
+fn test_find_device() {
+    use rsoundio::DeviceQuery;
+    let sio = rsoundio::SoundIo::default();
+    sio.connect_backend(rsoundio::SioBackend::Dummy).unwrap();
+    assert_eq!(sio.find_output_device(&DeviceQuery::new()).err(),
+               Some(rsoundio::SioError::Invalid));
+    sio.flush_events();
+    let dev = sio.default_output_device().unwrap();
+    // an empty query prefers the default device
+    assert!(sio.find_output_device(&DeviceQuery::new()).unwrap() == dev);
+    let query = DeviceQuery::new()
+                    .id(dev.id().unwrap())
+                    .name_contains(dev.name().unwrap().to_uppercase())
+                    .raw(dev.is_raw())
+                    .format(dev.formats()[0])
+                    .sample_rate(dev.sample_rates()[0].max)
+                    .channel_count(dev.current_layout().channel_count());
+    assert!(query.matches(&dev));
+    assert!(sio.find_output_device(&query).unwrap() == dev);
+    assert_eq!(sio.find_input_device(&query).err(),
+               Some(rsoundio::SioError::NoSuchDevice));
+    for query in [DeviceQuery::new().id("no such id"),
+                  DeviceQuery::new().name_contains("no such name"),
+                  DeviceQuery::new().raw(!dev.is_raw()),
+                  DeviceQuery::new().sample_rate(1),
+                  DeviceQuery::new().channel_count(1000)] {
+        assert!(!query.matches(&dev));
+    }
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn test_find_device_regex() {
+    let sio = rsoundio::SoundIo::default();
+    sio.connect_backend(rsoundio::SioBackend::Dummy).unwrap();
+    sio.flush_events();
+    let dev = sio.default_input_device().unwrap();
+    let pattern = format!("^{}$", regex::escape(&dev.name().unwrap()));
+    let query = rsoundio::DeviceQuery::new().name_regex(regex::Regex::new(&pattern).unwrap());
+    assert!(sio.find_input_device(&query).unwrap() == dev);
+    let query = rsoundio::DeviceQuery::new().name_regex(regex::Regex::new("^$").unwrap());
+    assert_eq!(sio.find_input_device(&query).err(),
+               Some(rsoundio::SioError::NoSuchDevice));
+}
+
+#[test]
+fn test_device_watcher() {
+    let sio = rsoundio::SoundIo::default();
+    sio.connect_backend(rsoundio::SioBackend::Dummy).unwrap();
+    sio.flush_events();
+    let mut watcher = rsoundio::DeviceWatcher::new();
+    let events = watcher.poll(&sio);
+    let outputs = sio.output_device_count().unwrap() as usize;
+    let inputs = sio.input_device_count().unwrap() as usize;
+    assert_eq!(events.iter()
+                     .filter(|e| matches!(**e, rsoundio::DeviceEvent::OutputAdded(_)))
+                     .count(),
+               outputs);
+    assert_eq!(events.iter()
+                     .filter(|e| matches!(**e, rsoundio::DeviceEvent::InputAdded(_)))
+                     .count(),
+               inputs);
+    let default_output = rsoundio::DeviceInfo::new(&sio.default_output_device().unwrap());
+    assert!(events.contains(&rsoundio::DeviceEvent::DefaultOutputChanged(Some(default_output))));
+    // a rescan of unchanged devices reports nothing
+    sio.force_device_scan();
+    sio.flush_events();
+    assert_eq!(watcher.poll(&sio), vec![]);
+}
+
+struct MockSource(rsoundio::DeviceSnapshot);
+impl rsoundio::SnapshotSource for MockSource {
+    fn snapshot(&self) -> rsoundio::DeviceSnapshot {
+        self.0.clone()
+    }
+}
+
+fn device_info(id: &str, aim: rsoundio::SioDeviceAim) -> rsoundio::DeviceInfo {
+    rsoundio::DeviceInfo {
+        id: id.to_string(),
+        name: id.to_uppercase(),
+        aim: aim,
+        is_raw: false,
+        probe_error: None,
+    }
+}
+
+#[test]
+fn test_device_watcher_diff() {
+    use rsoundio::{DeviceEvent, DeviceSnapshot, DeviceWatcher, SioDeviceAim, SioError};
+    let mic = device_info("mic", SioDeviceAim::Input);
+    let speakers = device_info("speakers", SioDeviceAim::Output);
+    let headset = device_info("headset", SioDeviceAim::Output);
+    let mut source = MockSource(DeviceSnapshot {
+        inputs: vec![mic.clone()],
+        outputs: vec![speakers.clone()],
+        default_input: Some(0),
+        default_output: Some(0),
+    });
+    let mut watcher = DeviceWatcher::with_snapshot(source.0.clone());
+    assert_eq!(watcher.poll(&source), vec![]);
+
+    // the headset is plugged in and becomes the default, the indices shift
+    source.0.outputs.insert(0, headset.clone());
+    assert_eq!(watcher.poll(&source),
+               vec![DeviceEvent::OutputAdded(headset.clone()),
+                    DeviceEvent::DefaultOutputChanged(Some(headset.clone()))]);
+    source.0.default_output = Some(0);
+    assert_eq!(watcher.poll(&source), vec![]);
+
+    let mut broken_mic = mic.clone();
+    broken_mic.probe_error = Some(SioError::OpeningDevice);
+    source.0.inputs = vec![broken_mic.clone()];
+    source.0.outputs.remove(0);
+    source.0.default_output = None;
+    assert_eq!(watcher.poll(&source),
+               vec![DeviceEvent::ProbeErrorChanged(broken_mic.clone()),
+                    DeviceEvent::OutputRemoved(headset),
+                    DeviceEvent::DefaultOutputChanged(None)]);
+
+    source.0.inputs.clear();
+    source.0.default_input = None;
+    assert_eq!(watcher.poll(&source),
+               vec![DeviceEvent::InputRemoved(broken_mic),
+                    DeviceEvent::DefaultInputChanged(None)]);
+    assert_eq!(watcher.snapshot(), &source.0);
+}
+
+#[test]
+fn test_device_capabilities() {
+    let sio = rsoundio::SoundIo::default();
+    sio.connect_backend(rsoundio::SioBackend::Dummy).unwrap();
+    sio.flush_events();
+    let dev = sio.default_output_device().unwrap();
+    assert!(dev.probe_error().is_none());
+    assert!(!dev.id().unwrap().is_empty());
+    assert_eq!(dev.name().unwrap(), format!("{}", dev));
+    assert_eq!(dev.aim(), rsoundio::SioDeviceAim::Output);
+    assert!(!dev.is_raw());
+    let layouts = dev.layouts();
+    assert!(!layouts.is_empty());
+    assert!(layouts.iter().all(|l| dev.supports_layout(l)));
+    assert!(dev.supports_layout(&dev.current_layout()));
+    let formats = dev.formats();
+    assert!(!formats.is_empty());
+    assert!(formats.iter().all(|&f| dev.supports_format(f)));
+    let rates = dev.sample_rates();
+    assert!(!rates.is_empty());
+    assert!(rates.iter().all(|r| r.min <= r.max && dev.supports_sample_rate(r.min)));
+    if let Some(rate) = dev.sample_rate_current() {
+        assert!(rates.iter().any(|r| r.contains(rate)));
+    }
+    assert!(dev.software_latency_min() <= dev.software_latency_max());
+    let in_dev = sio.default_input_device().unwrap();
+    assert_eq!(in_dev.aim(), rsoundio::SioDeviceAim::Input);
+}
+
+#[test]
+fn test_ring_buffer() {
+    let sio = rsoundio::SoundIo::default();
+    assert!(sio.create_ring_buffer::<f32>(0).is_err());
+    assert!(sio.create_ring_buffer::<()>(16).is_err());
+    let rb = sio.create_ring_buffer::<f32>(1024).unwrap();
+    let capacity = rb.capacity();
+    assert!(capacity >= 1024);
+    let (producer, consumer) = rb.split();
+    assert_eq!(producer.free_count(), capacity);
+    assert_eq!(consumer.fill_count(), 0);
+    let mut buf = vec![0.0f32; capacity];
+    assert_eq!(consumer.read(&mut buf), 0);
+
+    let data: Vec<f32> = (0..capacity + 10).map(|i| i as f32).collect();
+    assert_eq!(producer.write(&data), capacity);
+    assert_eq!(producer.free_count(), 0);
+    assert_eq!(consumer.read(&mut buf[..10]), 10);
+    assert_eq!(&buf[..10], &data[..10]);
+    // wrap around the end of the buffer
+    assert_eq!(producer.write(&data[capacity..]), 10);
+    assert_eq!(consumer.read(&mut buf), capacity);
+    assert_eq!(&buf[..], &data[10..]);
+    assert_eq!(producer.write(&data[..5]), 5);
+    assert_eq!(consumer.clear(), 5);
+    assert_eq!(consumer.fill_count(), 0);
+
+    let writer = ::std::thread::spawn(move || {
+        let mut written = 0;
+        while written < data.len() {
+            written += producer.write(&data[written..]);
+        }
+        data
+    });
+    let mut received = Vec::new();
+    while received.len() < capacity + 10 {
+        let cnt = consumer.read(&mut buf);
+        received.extend_from_slice(&buf[..cnt]);
+    }
+    assert_eq!(received, writer.join().unwrap());
+}
+
+#[test]
+fn test_device_ref_count() {
+    let sio = rsoundio::SoundIo::default();
+    sio.connect_backend(rsoundio::SioBackend::Dummy).unwrap();
+    sio.flush_events();
+    let dev = sio.default_output_device().unwrap();
+    let ref_count = dev.ref_count();
+    {
+        let cloned = dev.clone();
+        assert_eq!(cloned, dev);
+        assert_eq!(dev.ref_count(), ref_count + 1);
+    }
+    assert_eq!(dev.ref_count(), ref_count);
+    let stream = dev.create_outstream().unwrap();
+    // the stream holds its own reference
+    let ref_count = dev.ref_count();
+    for _ in 0..10 {
+        stream.set_format(rsoundio::SioFormat::Float32LE).unwrap();
+        assert_eq!(stream.device(), dev);
+        assert_eq!(dev.ref_count(), ref_count);
+    }
+    drop(stream);
+    assert_eq!(dev.ref_count(), ref_count - 1);
+    let in_dev = sio.default_input_device().unwrap();
+    let in_stream = in_dev.create_instream().unwrap();
+    let ref_count = in_dev.ref_count();
+    for _ in 0..10 {
+        in_stream.set_format(rsoundio::SioFormat::Float32LE).unwrap();
+        assert_eq!(in_stream.device().ref_count(), ref_count + 1);
+        assert_eq!(in_dev.ref_count(), ref_count);
+    }
+}
+
+#[test]
+#[should_panic(expected = "devices changed")]
+fn test_soundio_callback_panic() {
+    let mut sio = rsoundio::SoundIo::default();
+    sio.register_devices_change_callback(|| panic!("devices changed"));
+    sio.connect_backend(rsoundio::SioBackend::Dummy).unwrap();
+    // the panic is resumed outside of libsoundio
+    sio.flush_events();
+}
+
+#[test]
+fn test_event_loop() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let changes = Arc::new(AtomicUsize::new(0));
+    let signals = Arc::new(AtomicUsize::new(0));
+    let (c, s) = (changes.clone(), signals.clone());
+    let events = rsoundio::EventLoop::spawn(move |sio| {
+                                                sio.register_devices_change_callback(move || {
+                                                    c.fetch_add(1, Ordering::SeqCst);
+                                                });
+                                                sio.register_events_signal_callback(move || {
+                                                    s.fetch_add(1, Ordering::SeqCst);
+                                                });
+                                                sio.connect_backend(rsoundio::SioBackend::Dummy)
+                                            },
+                                            |_| {})
+                     .unwrap();
+    assert!(events.is_running());
+    for _ in 0..100 {
+        if changes.load(Ordering::SeqCst) > 0 {
+            break;
+        }
+        ::std::thread::sleep(::std::time::Duration::from_millis(10));
+    }
+    let before = signals.load(Ordering::SeqCst);
+    events.stop();
+    assert_eq!(changes.load(Ordering::SeqCst), 1);
+    // stopping wakes the loop like `SoundIo::wakeup`
+    assert!(signals.load(Ordering::SeqCst) > before);
+
+    let result = rsoundio::EventLoop::spawn(|_| Err(rsoundio::SioError::Invalid), |_| {});
+    assert_eq!(result.err(), Some(rsoundio::SioError::Invalid));
+}
+
+#[test]
+#[should_panic(expected = "events received")]
+fn test_event_loop_panic() {
+    let events = rsoundio::EventLoop::spawn(|sio| sio.connect_backend(rsoundio::SioBackend::Dummy),
+                                            |_| panic!("events received"))
+                     .unwrap();
+    // the panic is resumed on the stopping thread
+    events.stop();
+}
+
+#[test]
+#[should_panic(expected = "setup failed")]
+fn test_event_loop_setup_panic() {
+    // the panic is resumed by `spawn` instead of returning an error
+    let _ = rsoundio::EventLoop::spawn(|_| -> rsoundio::SioResult<()> { panic!("setup failed") },
+                                       |_| {});
+}
@@ -0,0 +1,53 @@
+#![cfg(feature = "async")]
+
+extern crate futures;
+extern crate rsoundio;
+
+use futures::executor::block_on;
+use futures::future;
+use futures::StreamExt;
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+#[test]
+fn test_events_stream() {
+    let mut sio = rsoundio::SoundIo::default();
+    sio.connect_backend(rsoundio::SioBackend::Dummy).unwrap();
+    {
+        let mut events = sio.events();
+        // the Dummy backend emits its device list on the first flush
+        assert_eq!(block_on(events.next()),
+                   Some(rsoundio::SoundIoEvent::DevicesChanged));
+    }
+    assert!(sio.output_device_count().unwrap() > 0);
+}
+
+#[test]
+fn test_events_keep_callbacks() {
+    let mut sio = rsoundio::SoundIo::default();
+    sio.connect_backend(rsoundio::SioBackend::Dummy).unwrap();
+    let changes = Rc::new(Cell::new(0));
+    let c = changes.clone();
+    sio.register_devices_change_callback(move || c.set(c.get() + 1));
+    {
+        let mut events = sio.events();
+        assert_eq!(block_on(events.next()),
+                   Some(rsoundio::SoundIoEvent::DevicesChanged));
+        // the registered callback is called as well
+        assert_eq!(changes.get(), 1);
+    }
+    // dropping the stream restored the callback instead of dropping it
+    assert_eq!(Rc::strong_count(&changes), 2);
+    sio.register_devices_change_callback(|| {});
+    assert_eq!(Rc::strong_count(&changes), 1);
+}
+
+#[test]
+fn test_wait_events_async() {
+    let sio = rsoundio::SoundIo::default();
+    sio.connect_backend(rsoundio::SioBackend::Dummy).unwrap();
+    // the wakeup is sent after the first poll of the waiting future
+    block_on(future::join(sio.wait_events_async(), future::lazy(|_| sio.wakeup())));
+    assert!(sio.output_device_count().unwrap() > 0);
+}
@@ -3,6 +3,9 @@ extern crate rci;
 
 use rci::Ci;
 
+use std::f32::consts::FRAC_1_SQRT_2;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::thread;
 use std::time::Duration;
 
@@ -51,3 +54,386 @@ fn test_outstream() {
     assert!(stream.clear_buffer().is_none());
     thread::sleep(Duration::new(1, 0));
 }
+
+#[test]
+fn test_instream() {
+    if Ci::new().is_some() {
+        ::std::process::exit(0)
+    }
+    let sio = rsoundio::SoundIo::default();
+    sio.connect_backend(rsoundio::SioBackend::Dummy).unwrap();
+    sio.flush_events();
+    let dev_idx = sio.default_input_device_index().unwrap();
+    let dev = sio.input_device(dev_idx).unwrap();
+    let mut stream = dev.create_instream().unwrap();
+    stream.set_format(rsoundio::SioFormat::Float32LE).unwrap();
+    assert!(stream.set_name("recorder").is_ok());
+    assert_eq!(stream.name().unwrap(), "recorder");
+    let cb = |input: rsoundio::InStream, _: u32, max_frame_count: u32| {
+        let frames = input.read_stream_f32(max_frame_count).unwrap();
+        assert_eq!(frames.len(), input.layout().channel_count() as usize);
+    };
+    stream.register_read_callback(cb);
+    let ocb = |_: rsoundio::InStream| println!("Overflow!");
+    stream.register_overflow_callback(ocb);
+    let ecb = |_: rsoundio::InStream, err: rsoundio::SioError| println!("Error: {}", err);
+    stream.register_error_callback(ecb);
+    stream.open().unwrap();
+    assert!(stream.layout_error().is_ok());
+    assert!(stream.sample_rate() > 0);
+    stream.start().unwrap();
+    thread::sleep(Duration::new(1, 0));
+    assert!(stream.pause().is_none());
+    thread::sleep(Duration::new(1, 0));
+    assert!(stream.unpause().is_none());
+    thread::sleep(Duration::new(1, 0));
+}
+
+/// Connects to the dummy backend, the tests are skipped on CI.
+fn dummy_sio() -> rsoundio::SoundIo {
+    if Ci::new().is_some() {
+        ::std::process::exit(0)
+    }
+    let sio = rsoundio::SoundIo::default();
+    sio.connect_backend(rsoundio::SioBackend::Dummy).unwrap();
+    sio.flush_events();
+    sio
+}
+
+/// Creates a `Float32LE` stream on `dev`.
+fn f32_outstream<'a>(dev: &rsoundio::Device<'a>) -> rsoundio::OutStream<'a> {
+    let stream = dev.create_outstream().unwrap();
+    stream.set_format(rsoundio::SioFormat::Float32LE).unwrap();
+    stream
+}
+
+/// Opens and starts `stream` with `write` as write callback,
+/// which returns the number of frames it wrote. The frames are added to `frames`.
+fn started_outstream<'a, W>(mut stream: rsoundio::OutStream<'a>,
+                            frames: Arc<AtomicUsize>,
+                            mut write: W)
+                            -> rsoundio::OutStream<'a>
+    where W: FnMut(&mut rsoundio::OutStream, u32, u32) -> usize + 'a
+{
+    stream.register_write_callback(move |mut out: rsoundio::OutStream, min_frame_count: u32, max_frame_count: u32| {
+        let cnt = write(&mut out, min_frame_count, max_frame_count);
+        frames.fetch_add(cnt, Ordering::SeqCst);
+    });
+    stream.register_underflow_callback(|_: rsoundio::OutStream| {});
+    stream.register_error_callback(|_: rsoundio::OutStream, err: rsoundio::SioError| {
+        panic!("Error: {}", err)
+    });
+    stream.open().unwrap();
+    stream.start().unwrap();
+    stream
+}
+
+/// Lets `stream` play for a while and returns the number of frames written so far.
+fn played_frames(stream: &rsoundio::OutStream, frames: &AtomicUsize) -> usize {
+    thread::sleep(Duration::from_millis(200));
+    // a failed assertion inside of a callback turns into a fault
+    assert!(stream.fault().is_none());
+    frames.load(Ordering::SeqCst)
+}
+
+#[test]
+fn test_move_started_outstream() {
+    let sio = dummy_sio();
+    let dev = sio.default_output_device().unwrap();
+    let frames = Arc::new(AtomicUsize::new(0));
+    // the stream is moved out of the function that registered the callbacks
+    let stream = started_outstream(f32_outstream(&dev), frames.clone(), |out, _, max_frame_count| {
+        let channel_count = out.layout().channel_count() as usize;
+        let buffers = vec![vec![0.0f32; max_frame_count as usize]; channel_count];
+        out.write_stream_f32(0, &buffers).unwrap() as usize
+    });
+    let before = played_frames(&stream, &frames);
+    let streams = vec![stream];
+    let mut boxed = Box::new(streams);
+    thread::sleep(Duration::from_millis(300));
+    assert!(frames.load(Ordering::SeqCst) > before);
+    let stream = boxed.pop().unwrap();
+    assert!(stream.fault().is_none());
+    assert!(stream.pause().is_none());
+    drop(stream);
+}
+
+#[test]
+fn test_outstream_callback_panic() {
+    let sio = dummy_sio();
+    let dev = sio.default_output_device().unwrap();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let c = calls.clone();
+    let mut stream = dev.create_outstream().unwrap();
+    stream.register_write_callback(move |_: rsoundio::OutStream, _: u32, _: u32| {
+        c.fetch_add(1, Ordering::SeqCst);
+        panic!("write callback failed");
+    });
+    stream.open().unwrap();
+    assert!(stream.fault().is_none());
+    stream.start().unwrap();
+    thread::sleep(Duration::from_millis(500));
+    assert_eq!(stream.fault(), Some(rsoundio::StreamFault::WritePanicked));
+    // the callback is never called again, silence is written instead
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert!(stream.pause().is_none());
+}
+
+#[test]
+fn test_outstream_callback_panic_after_write() {
+    let sio = dummy_sio();
+    let dev = sio.default_output_device().unwrap();
+    let mut stream = f32_outstream(&dev);
+    let max_frames = Arc::new(AtomicUsize::new(0));
+    let written = Arc::new(AtomicUsize::new(0));
+    let (m, w) = (max_frames.clone(), written.clone());
+    stream.register_write_callback(move |out: rsoundio::OutStream, _: u32, max_frame_count: u32| {
+        let channel_count = out.layout().channel_count() as usize;
+        let half = max_frame_count as usize / 2;
+        // the shorter last channel limits the written frames instead of panicking
+        let mut frames = vec![vec![0.5f32; max_frame_count as usize]; channel_count];
+        frames[channel_count - 1].truncate(half);
+        let cnt = out.write_stream_f32(0, &frames).unwrap();
+        m.store(max_frame_count as usize, Ordering::SeqCst);
+        w.store(cnt as usize, Ordering::SeqCst);
+        panic!("write callback failed after writing");
+    });
+    stream.open().unwrap();
+    stream.start().unwrap();
+    thread::sleep(Duration::from_millis(500));
+    assert_eq!(stream.fault(), Some(rsoundio::StreamFault::WritePanicked));
+    let max_frames = max_frames.load(Ordering::SeqCst);
+    assert!(max_frames > 1);
+    // the frames of the panicking callback were played, only the rest of
+    // the chunk was filled with silence, otherwise libsoundio would have
+    // rejected the silence for exceeding `max_frame_count`
+    assert_eq!(written.load(Ordering::SeqCst), max_frames / 2);
+    assert!(stream.silence_error().is_none());
+    assert!(stream.pause().is_none());
+}
+
+#[test]
+fn test_outstream_begin_write() {
+    let sio = dummy_sio();
+    let dev = sio.default_output_device().unwrap();
+    let frames = Arc::new(AtomicUsize::new(0));
+    let stream = started_outstream(f32_outstream(&dev), frames.clone(), |out, _, max_frame_count| {
+        let mut guard = out.begin_write(max_frame_count).unwrap();
+        // the sample type has to match the format, not only its size
+        assert_eq!(guard.channels::<f64>().err(), Some(rsoundio::SioError::Invalid));
+        assert_eq!(guard.channels::<i32>().err(), Some(rsoundio::SioError::Invalid));
+        assert_eq!(guard.channel::<u32>(0).err(), Some(rsoundio::SioError::Invalid));
+        assert_eq!(guard.channel::<rsoundio::I24>(0).err(),
+                   Some(rsoundio::SioError::Invalid));
+        assert_eq!(guard.channel::<f32>(guard.channel_count()).err(),
+                   Some(rsoundio::SioError::Invalid));
+        let frame_count = guard.frame_count();
+        for (ch, mut channel) in guard.channels::<f32>().unwrap().enumerate() {
+            assert_eq!(channel.len(), frame_count);
+            for (idx, sample) in channel.iter_mut().enumerate() {
+                *sample = (idx as f32 * 0.01).sin();
+            }
+            channel[0] = ch as f32;
+            // the samples are written in place
+            assert_eq!(channel[0], ch as f32);
+            assert!(channel.iter().enumerate().skip(1).all(|(idx, &sample)| {
+                sample == (idx as f32 * 0.01).sin()
+            }));
+        }
+        guard.end().unwrap();
+        frame_count
+    });
+    assert!(played_frames(&stream, &frames) > 0);
+}
+
+#[test]
+fn test_outstream_write_interleaved() {
+    let sio = dummy_sio();
+    let dev = sio.default_output_device().unwrap();
+    let frames = Arc::new(AtomicUsize::new(0));
+    let calls = Arc::new(AtomicUsize::new(0));
+    let c = calls.clone();
+    let stream = started_outstream(f32_outstream(&dev), frames.clone(), move |out, _, max_frame_count| {
+        let channel_count = out.layout().channel_count() as usize;
+        let frame_count = max_frame_count as usize;
+        match c.fetch_add(1, Ordering::SeqCst) % 3 {
+            0 => {
+                let samples = vec![0.25f32; frame_count * channel_count];
+                assert_eq!(out.write_interleaved(&samples[1..]),
+                           Err(rsoundio::SioError::Invalid));
+                assert_eq!(out.write_interleaved(&samples).unwrap(), frame_count);
+                frame_count
+            }
+            1 => {
+                // an endless iterator is limited by the frame count
+                let samples = (0..).map(|idx| [(idx as f32 * 0.01).sin(); 2]);
+                assert_eq!(out.write_from_iter(max_frame_count, samples).unwrap(),
+                           frame_count);
+                frame_count
+            }
+            _ => {
+                // a short iterator writes its frames, the rest of the chunk is silence
+                let samples = vec![[0.5f32; 2]; frame_count / 2];
+                let cnt = out.write_from_iter(max_frame_count, samples).unwrap();
+                assert_eq!(cnt, frame_count / 2);
+                cnt
+            }
+        }
+    });
+    assert!(played_frames(&stream, &frames) > 0);
+    assert!(calls.load(Ordering::SeqCst) > 2);
+}
+
+#[test]
+fn test_outstream_sample_conversion() {
+    let sio = dummy_sio();
+    let dev = sio.default_output_device().unwrap();
+    let formats = [rsoundio::SioFormat::S16BE,
+                   rsoundio::SioFormat::U16LE,
+                   rsoundio::SioFormat::S24LE,
+                   rsoundio::SioFormat::U24BE,
+                   rsoundio::SioFormat::S32LE,
+                   rsoundio::SioFormat::Float64BE];
+    for &format in formats.iter().filter(|&&format| dev.supports_format(format)) {
+        let stream = dev.create_outstream().unwrap();
+        stream.set_format(format).unwrap();
+        let frames = Arc::new(AtomicUsize::new(0));
+        let stream = started_outstream(stream, frames.clone(), |out, _, max_frame_count| {
+            // f32 samples are converted to the format of the stream
+            let samples = (0..max_frame_count as usize)
+                              .map(|idx| [(idx as f32 * 0.01).sin(); 2]);
+            let cnt = out.write_from_iter(max_frame_count, samples).unwrap();
+            assert_eq!(cnt, max_frame_count as usize);
+            cnt
+        });
+        assert_eq!(stream.format().unwrap(), format);
+        assert!(played_frames(&stream, &frames) > 0);
+    }
+}
+
+#[test]
+fn test_instream_24_bit() {
+    let sio = dummy_sio();
+    let dev = sio.default_input_device().unwrap();
+    if !dev.supports_format(rsoundio::SioFormat::S24LE) {
+        return;
+    }
+    let mut stream = dev.create_instream().unwrap();
+    stream.set_format(rsoundio::SioFormat::S24LE).unwrap();
+    let frames = Arc::new(AtomicUsize::new(0));
+    let f = frames.clone();
+    stream.register_read_callback(move |input: rsoundio::InStream, _: u32, max_frame_count: u32| {
+        let buffers = input.read_stream_i24(max_frame_count).unwrap();
+        assert_eq!(buffers.len(), input.layout().channel_count() as usize);
+        f.fetch_add(buffers[0].len(), Ordering::SeqCst);
+    });
+    stream.open().unwrap();
+    stream.start().unwrap();
+    thread::sleep(Duration::from_millis(500));
+    assert!(stream.fault().is_none());
+    assert!(frames.load(Ordering::SeqCst) > 0);
+}
+
+#[test]
+fn test_outstream_layout() {
+    let sio = dummy_sio();
+    let dev = sio.default_output_device().unwrap();
+    let stream = f32_outstream(&dev);
+    let initial = stream.layout();
+    // a failed call leaves the layout untouched
+    assert_eq!(stream.set_channel_count(0), Err(rsoundio::SioError::Invalid));
+    assert_eq!(stream.layout(), initial);
+    let odd = rsoundio::ChannelLayout::from_channels(&[rsoundio::SioChannelId::TopCenter,
+                                                       rsoundio::SioChannelId::BottomCenter,
+                                                       rsoundio::SioChannelId::Aux0])
+                  .unwrap();
+    assert!(!dev.supports_layout(&odd));
+    assert_eq!(stream.set_layout(&odd),
+               Err(rsoundio::SioError::IncompatibleDevice));
+    assert_eq!(stream.layout(), initial);
+    let mono = rsoundio::ChannelLayout::from_id(rsoundio::SioChannelLayoutId::Mono);
+    if dev.supports_layout(&mono) {
+        stream.set_channel_count(1).unwrap();
+        assert_eq!(stream.layout(), mono);
+        assert_eq!(stream.layout().channel_count(), 1);
+    }
+    let surround = rsoundio::ChannelLayout::default(6).unwrap();
+    if !dev.supports_layout(&surround) {
+        assert_eq!(stream.set_layout(&surround),
+                   Err(rsoundio::SioError::IncompatibleDevice));
+        return;
+    }
+    stream.set_layout(&surround).unwrap();
+    assert_eq!(stream.layout(), surround);
+    assert_eq!(stream.layout().channel_count(), 6);
+    assert_eq!(stream.layout().channels(), surround.channels());
+    let channels = Arc::new(AtomicUsize::new(0));
+    let c = channels.clone();
+    let frames = Arc::new(AtomicUsize::new(0));
+    let stream = started_outstream(stream, frames.clone(), move |out, _, max_frame_count| {
+        let channel_count = out.layout().channel_count() as usize;
+        let samples = vec![0.0f32; max_frame_count as usize * channel_count];
+        c.store(channel_count, Ordering::SeqCst);
+        out.write_interleaved(&samples).unwrap()
+    });
+    assert_eq!(stream.layout_error(), Ok(()));
+    // opening the stream keeps the layout
+    assert_eq!(stream.layout(), surround);
+    assert!(played_frames(&stream, &frames) > 0);
+    assert_eq!(channels.load(Ordering::SeqCst), 6);
+}
+
+#[test]
+fn test_outstream_write_mixed() {
+    let sio = dummy_sio();
+    let dev = sio.default_output_device().unwrap();
+    let stream = dev.create_outstream().unwrap();
+    let surround = rsoundio::ChannelLayout::from_id(rsoundio::SioChannelLayoutId::FivePointOne);
+    let mixer = rsoundio::ChannelMixer::new(&surround, &stream.layout());
+    // the buffer is allocated up front, mixing in the callback doesn't allocate
+    let mut content = vec![0.0f32; 6 * 4096];
+    for (idx, sample) in content.iter_mut().enumerate() {
+        *sample = (idx as f32 * 0.001).sin();
+    }
+    // the front channels of the 5.1 content are copied, the center is spread to both
+    let mut out = [0.0f32; 2];
+    mixer.mix(&[0.5, -0.5, 1.0, 0.0, 0.0, 0.0], &mut out);
+    assert_eq!(out, [0.5 + FRAC_1_SQRT_2, -0.5 + FRAC_1_SQRT_2]);
+    // more channels than libsoundio supports
+    let wide = rsoundio::ChannelMixer::silent(6, 25);
+    let frames = Arc::new(AtomicUsize::new(0));
+    let stream = started_outstream(stream, frames.clone(), move |out, _, max_frame_count| {
+        let frame_count = ::std::cmp::min(max_frame_count as usize, 4096);
+        assert_eq!(out.write_mixed(&mixer, &content[..5]), Err(rsoundio::SioError::Invalid));
+        assert_eq!(out.write_mixed(&wide, &content[..6]), Err(rsoundio::SioError::Invalid));
+        let cnt = out.write_mixed(&mixer, &content[..frame_count * 6]).unwrap();
+        assert_eq!(cnt, frame_count);
+        cnt
+    });
+    assert!(played_frames(&stream, &frames) > 0);
+}
+
+#[test]
+fn test_outstream_write_routed() {
+    let sio = dummy_sio();
+    let dev = sio.default_output_device().unwrap();
+    let stream = dev.create_outstream().unwrap();
+    // a mono signal on the right channel only
+    let mut routing = stream.routing(1);
+    routing.route(0, rsoundio::SioChannelId::FrontRight, 0.5).unwrap();
+    assert_eq!(routing.layout(), &stream.layout());
+    let right = stream.layout().find_channel(rsoundio::SioChannelId::FrontRight).unwrap();
+    let mut out = [1.0f32; 2];
+    routing.mixer().mix(&[0.8], &mut out);
+    assert_eq!(out[right as usize], 0.4);
+    assert_eq!(out[1 - right as usize], 0.0);
+    let content: Vec<f32> = (0..4096).map(|idx| (idx as f32 * 0.01).sin()).collect();
+    let frames = Arc::new(AtomicUsize::new(0));
+    let stream = started_outstream(stream, frames.clone(), move |out, _, max_frame_count| {
+        let frame_count = ::std::cmp::min(max_frame_count as usize, content.len());
+        let cnt = out.write_routed(&routing, &content[..frame_count]).unwrap();
+        assert_eq!(cnt, frame_count);
+        cnt
+    });
+    assert!(played_frames(&stream, &frames) > 0);
+}